@@ -0,0 +1,416 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use hotline_bookmark::address::InvalidAddress;
+use hotline_bookmark::bookmark::Bookmark;
+use macroman_tools::{macroman_to_string, string_to_macroman};
+
+// establish connection
+// send TRTP/HOTL handshake
+// receive TRTP reply
+// optionally send a login transaction and wait for its reply
+
+const HANDSHAKE: &[u8; 12] = b"TRTPHOTL\x00\x01\x00\x02";
+const HANDSHAKE_REPLY_LEN: usize = 8;
+
+const TRANSACTION_HEADER_LEN: usize = 20;
+const LOGIN_TRANSACTION: u16 = 107;
+const LOGIN_TRANSACTION_ID: u32 = 1;
+
+const ERROR_TEXT_FIELD: u16 = 100;
+const USER_NAME_FIELD: u16 = 102;
+const USER_LOGIN_FIELD: u16 = 105;
+const USER_PASSWORD_FIELD: u16 = 106;
+const VERSION_FIELD: u16 = 160;
+
+/// the client version we log in as, the same as Hotline 1.5.1
+const CLIENT_VERSION: u16 = 151;
+
+/// transactions bigger than this aren't from a Hotline server we want to talk to
+const MAX_TRANSACTION_LEN: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy)]
+pub struct CheckOptions {
+    /// how long to wait to connect, and then for each reply
+    pub timeout: Duration,
+
+    /// log in with the bookmark's username and password after the handshake
+    pub login: bool,
+}
+
+/// what happened when we tried to reach a bookmark's server
+#[derive(Debug)]
+pub enum Status {
+    InvalidAddress(InvalidAddress),
+    Unresolved(io::Error),
+    Refused,
+    TimedOut,
+    BadProtocol(String),
+    Failed(io::Error),
+    Reachable,
+    LoginAccepted,
+    LoginRejected(String),
+}
+
+impl Status {
+    /// whether the server answered, and took the login if we tried one
+    pub fn is_ok(&self) -> bool {
+        matches!(self, Status::Reachable | Status::LoginAccepted)
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::InvalidAddress(err) => write!(f, "{err}"),
+            Status::Unresolved(err) => write!(f, "couldn't look up the host: {err}"),
+            Status::Refused => write!(f, "connection refused"),
+            Status::TimedOut => write!(f, "timed out"),
+            Status::BadProtocol(reason) => write!(f, "not a Hotline server ({reason})"),
+            Status::Failed(err) => write!(f, "couldn't connect: {err}"),
+            Status::Reachable => write!(f, "reachable"),
+            Status::LoginAccepted => write!(f, "reachable, login accepted"),
+            Status::LoginRejected(reason) => write!(f, "reachable, login rejected: {reason}"),
+        }
+    }
+}
+
+impl From<io::Error> for Status {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::ConnectionRefused => Status::Refused,
+            // read timeouts are WouldBlock on some platforms
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => Status::TimedOut,
+            _ => Status::Failed(err),
+        }
+    }
+}
+
+/// connect to the bookmark's server and see whether it speaks Hotline
+pub fn check(bookmark: &Bookmark, options: &CheckOptions) -> Status {
+    let address = match bookmark.server_address() {
+        Ok(address) => address,
+        Err(err) => return Status::InvalidAddress(err),
+    };
+
+    let addrs = match (address.host.as_str(), address.port).to_socket_addrs() {
+        Ok(addrs) => addrs,
+        Err(err) => return Status::Unresolved(err),
+    };
+
+    // a hostname can have several addresses. try each of them until one works.
+    let mut result = Err(io::ErrorKind::NotFound.into());
+    for addr in addrs {
+        result = TcpStream::connect_timeout(&addr, options.timeout);
+        if result.is_ok() {
+            break;
+        }
+    }
+
+    let status = result.and_then(|mut stream| {
+        stream.set_read_timeout(Some(options.timeout))?;
+        stream.set_write_timeout(Some(options.timeout))?;
+
+        if let Err(status) = handshake(&mut stream)? {
+            return Ok(status);
+        }
+
+        if options.login {
+            login(&mut stream, bookmark)
+        } else {
+            Ok(Status::Reachable)
+        }
+    });
+
+    status.unwrap_or_else(Status::from)
+}
+
+/// send the handshake and read the reply. The inner error is for servers that aren't Hotline
+/// servers, or won't talk to us.
+fn handshake(stream: &mut TcpStream) -> io::Result<Result<(), Status>> {
+    stream.write_all(HANDSHAKE)?;
+
+    let mut reply = [0; HANDSHAKE_REPLY_LEN];
+    if let Err(err) = stream.read_exact(&mut reply) {
+        return match err.kind() {
+            io::ErrorKind::UnexpectedEof => Ok(Err(Status::BadProtocol(
+                "it closed the connection during the handshake".into(),
+            ))),
+            _ => Err(err),
+        };
+    }
+
+    if &reply[..4] != b"TRTP" {
+        return Ok(Err(Status::BadProtocol(format!(
+            "it sent {:?} instead of \"TRTP\"",
+            String::from_utf8_lossy(&reply[..4])
+        ))));
+    }
+
+    let error_code = u32::from_be_bytes(reply[4..].try_into().unwrap());
+    if error_code != 0 {
+        return Ok(Err(Status::BadProtocol(format!(
+            "it refused the handshake with error {error_code}"
+        ))));
+    }
+
+    Ok(Ok(()))
+}
+
+/// usernames and passwords are sent with every byte inverted
+fn obfuscate(s: &str) -> Vec<u8> {
+    string_to_macroman(s).iter().map(|b| !b).collect()
+}
+
+fn put_field(data: &mut Vec<u8>, id: u16, value: &[u8]) {
+    data.extend(id.to_be_bytes());
+    data.extend((value.len() as u16).to_be_bytes());
+    data.extend(value);
+}
+
+/// the login transaction for a bookmark. Blank usernames and passwords are left out, which
+/// servers take as the guest account.
+fn login_transaction(bookmark: &Bookmark) -> Vec<u8> {
+    let mut fields = vec![];
+    let mut count: u16 = 0;
+    if !bookmark.username.is_empty() {
+        put_field(
+            &mut fields,
+            USER_LOGIN_FIELD,
+            &obfuscate(&bookmark.username),
+        );
+        count += 1;
+    }
+    if !bookmark.password.is_empty() {
+        put_field(
+            &mut fields,
+            USER_PASSWORD_FIELD,
+            &obfuscate(&bookmark.password),
+        );
+        count += 1;
+    }
+    put_field(&mut fields, USER_NAME_FIELD, b"hlbm");
+    put_field(&mut fields, VERSION_FIELD, &CLIENT_VERSION.to_be_bytes());
+    count += 2;
+
+    let data_len = (fields.len() + 2) as u32;
+
+    let mut transaction = Vec::with_capacity(TRANSACTION_HEADER_LEN + data_len as usize);
+    transaction.extend([0, 0]); // flags, is reply
+    transaction.extend(LOGIN_TRANSACTION.to_be_bytes());
+    transaction.extend(LOGIN_TRANSACTION_ID.to_be_bytes());
+    transaction.extend(0u32.to_be_bytes()); // error code
+    transaction.extend(data_len.to_be_bytes()); // total size
+    transaction.extend(data_len.to_be_bytes()); // data size
+    transaction.extend(count.to_be_bytes());
+    transaction.extend(fields);
+
+    transaction
+}
+
+/// find a field in a transaction's data
+fn find_field(data: &[u8], id: u16) -> Option<&[u8]> {
+    let count = u16::from_be_bytes(data.get(..2)?.try_into().ok()?);
+
+    let mut rest = &data[2..];
+    for _ in 0..count {
+        let field_id = u16::from_be_bytes(rest.get(..2)?.try_into().ok()?);
+        let len = u16::from_be_bytes(rest.get(2..4)?.try_into().ok()?) as usize;
+        let value = rest.get(4..4 + len)?;
+        if field_id == id {
+            return Some(value);
+        }
+        rest = &rest[4 + len..];
+    }
+
+    None
+}
+
+fn login(stream: &mut TcpStream, bookmark: &Bookmark) -> io::Result<Status> {
+    stream.write_all(&login_transaction(bookmark))?;
+
+    // servers can send other transactions before the reply to ours, so skip them
+    loop {
+        let mut header = [0; TRANSACTION_HEADER_LEN];
+        if let Err(err) = stream.read_exact(&mut header) {
+            return match err.kind() {
+                io::ErrorKind::UnexpectedEof => Ok(Status::LoginRejected(
+                    "the server closed the connection".into(),
+                )),
+                _ => Err(err),
+            };
+        }
+
+        let is_reply = header[1] == 1;
+        let id = u32::from_be_bytes(header[4..8].try_into().unwrap());
+        let error_code = u32::from_be_bytes(header[8..12].try_into().unwrap());
+        let data_len = u32::from_be_bytes(header[16..20].try_into().unwrap()) as usize;
+
+        if data_len > MAX_TRANSACTION_LEN {
+            return Ok(Status::BadProtocol(format!(
+                "it sent a {data_len} byte transaction"
+            )));
+        }
+
+        let mut data = vec![0; data_len];
+        stream.read_exact(&mut data)?;
+
+        if !is_reply || id != LOGIN_TRANSACTION_ID {
+            continue;
+        }
+
+        if error_code == 0 {
+            return Ok(Status::LoginAccepted);
+        }
+
+        let reason = match find_field(&data, ERROR_TEXT_FIELD) {
+            Some(text) => macroman_to_string(text),
+            None => format!("error {error_code}"),
+        };
+        return Ok(Status::LoginRejected(reason));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn options(login: bool) -> CheckOptions {
+        CheckOptions {
+            timeout: Duration::from_millis(500),
+            login,
+        }
+    }
+
+    /// a fake server that reads the client's handshake and then hands the connection to `serve`
+    fn fake_server<F>(serve: F) -> Bookmark
+    where
+        F: FnOnce(TcpStream) + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut handshake = [0; 12];
+            stream.read_exact(&mut handshake).unwrap();
+            assert_eq!(&handshake, HANDSHAKE);
+
+            serve(stream);
+        });
+
+        Bookmark::new(address.to_string())
+    }
+
+    /// reply to a login, checking the username and password that were sent
+    fn reply_to_login(mut stream: TcpStream, password: &str, reply: &[u8]) {
+        stream.write_all(b"TRTP\0\0\0\0").unwrap();
+
+        let mut header = [0; TRANSACTION_HEADER_LEN];
+        stream.read_exact(&mut header).unwrap();
+        assert_eq!(&header[2..4], &LOGIN_TRANSACTION.to_be_bytes());
+        let len = u32::from_be_bytes(header[16..20].try_into().unwrap()) as usize;
+        let mut data = vec![0; len];
+        stream.read_exact(&mut data).unwrap();
+
+        assert_eq!(
+            find_field(&data, USER_LOGIN_FIELD),
+            Some(&obfuscate("guest")[..])
+        );
+        let accepted = find_field(&data, USER_PASSWORD_FIELD) == Some(&obfuscate(password)[..]);
+
+        // something unrelated first, like a chat message
+        let mut chat = [0; TRANSACTION_HEADER_LEN];
+        chat[2..4].copy_from_slice(&106u16.to_be_bytes());
+        stream.write_all(&chat).unwrap();
+
+        let mut header = [0; TRANSACTION_HEADER_LEN];
+        header[1] = 1;
+        header[4..8].copy_from_slice(&LOGIN_TRANSACTION_ID.to_be_bytes());
+        if !accepted {
+            header[8..12].copy_from_slice(&1u32.to_be_bytes());
+            header[12..16].copy_from_slice(&(reply.len() as u32).to_be_bytes());
+            header[16..20].copy_from_slice(&(reply.len() as u32).to_be_bytes());
+            stream.write_all(&header).unwrap();
+            stream.write_all(reply).unwrap();
+        } else {
+            stream.write_all(&header).unwrap();
+        }
+
+        // wait for the client to hang up
+        let _ = stream.read(&mut [0; 1]);
+    }
+
+    #[test]
+    fn it_reaches_hotline_servers() {
+        let bookmark = fake_server(|mut stream| {
+            stream.write_all(b"TRTP\0\0\0\0").unwrap();
+        });
+        assert!(matches!(
+            check(&bookmark, &options(false)),
+            Status::Reachable
+        ));
+
+        let bookmark = fake_server(|mut stream| {
+            stream.write_all(b"HTTP/1.1 400 Bad Request\r\n").unwrap();
+        });
+        let status = check(&bookmark, &options(false));
+        assert_eq!(
+            status.to_string(),
+            "not a Hotline server (it sent \"HTTP\" instead of \"TRTP\")"
+        );
+
+        let bookmark = fake_server(|stream| {
+            // never reply
+            thread::sleep(Duration::from_secs(2));
+            drop(stream);
+        });
+        assert!(matches!(
+            check(&bookmark, &options(false)),
+            Status::TimedOut
+        ));
+
+        // nothing's listening once the listener is dropped
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let bookmark = Bookmark::new(listener.local_addr().unwrap().to_string());
+        drop(listener);
+        assert!(matches!(check(&bookmark, &options(false)), Status::Refused));
+
+        let bookmark = Bookmark::new("not a host".into());
+        assert!(matches!(
+            check(&bookmark, &options(false)),
+            Status::InvalidAddress(_)
+        ));
+    }
+
+    #[test]
+    fn it_logs_in() {
+        let mut reply = vec![];
+        reply.extend(1u16.to_be_bytes());
+        put_field(&mut reply, ERROR_TEXT_FIELD, b"Incorrect login.");
+
+        let rejected = reply.clone();
+        let mut bookmark = fake_server(move |stream| reply_to_login(stream, "secret", &rejected));
+        bookmark
+            .credentials("guest".into(), "wrong".into())
+            .unwrap();
+        assert_eq!(
+            check(&bookmark, &options(true)).to_string(),
+            "reachable, login rejected: Incorrect login."
+        );
+
+        let mut bookmark = fake_server(move |stream| reply_to_login(stream, "secret", &reply));
+        bookmark
+            .credentials("guest".into(), "secret".into())
+            .unwrap();
+        assert!(matches!(
+            check(&bookmark, &options(true)),
+            Status::LoginAccepted
+        ));
+    }
+}
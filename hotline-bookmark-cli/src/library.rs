@@ -0,0 +1,178 @@
+use std::path::{Path, PathBuf};
+
+use hotline_bookmark::bookmark::{bookmark_path, Bookmark};
+
+use clap::ArgEnum;
+use serde::{Deserialize, Serialize};
+
+/// every bookmark in a directory, in one document
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Library {
+    pub bookmarks: Vec<LibraryEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LibraryEntry {
+    /// the bookmark's file name, without its extension
+    pub name: String,
+
+    #[serde(flatten)]
+    pub bookmark: Bookmark,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug)]
+pub enum LibraryFormat {
+    Json,
+    Toml,
+}
+
+impl LibraryFormat {
+    /// TOML for `.toml` files, JSON for anything else
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => LibraryFormat::Toml,
+            _ => LibraryFormat::Json,
+        }
+    }
+
+    pub fn to_string(self, library: &Library) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(match self {
+            LibraryFormat::Json => serde_json::to_string_pretty(library)?,
+            LibraryFormat::Toml => toml::to_string(library)?,
+        })
+    }
+
+    pub fn parse(self, s: &str) -> Result<Library, Box<dyn std::error::Error>> {
+        Ok(match self {
+            LibraryFormat::Json => serde_json::from_str(s)?,
+            LibraryFormat::Toml => toml::from_str(s)?,
+        })
+    }
+}
+
+/// read every bookmark in `dir`. Hidden files, like AppleDouble `._` files, are skipped, and so is
+/// anything that isn't a bookmark, with a warning.
+pub fn export(dir: &Path, redact_passwords: bool) -> Result<Library, Box<dyn std::error::Error>> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    paths.sort();
+
+    let mut bookmarks = vec![];
+    for path in paths {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        if file_name.starts_with('.') || !path.is_file() {
+            continue;
+        }
+
+        let mut bookmark = match Bookmark::from_file(&path.to_string_lossy()) {
+            Ok(bookmark) => bookmark,
+            Err(err) => {
+                eprintln!("Skipping {}: {err}", path.display());
+                continue;
+            }
+        };
+
+        if redact_passwords {
+            bookmark.password = "".into();
+        }
+
+        let name = match path.extension() {
+            Some(ext) if ext == "hlbm" || ext == "bin" => path.file_stem().unwrap_or_default(),
+            _ => path.file_name().unwrap_or_default(),
+        };
+
+        bookmarks.push(LibraryEntry {
+            name: name.to_string_lossy().into_owned(),
+            bookmark,
+        });
+    }
+
+    Ok(Library { bookmarks })
+}
+
+/// write a bookmark file for every entry into `dir`, without overwriting any that are already there
+pub fn import(library: &Library, dir: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    // check every bookmark can be written first, so a bad one doesn't leave half an import
+    for entry in &library.bookmarks {
+        entry
+            .bookmark
+            .to_bytes()
+            .map_err(|err| format!("{}: {err}", entry.name))?;
+    }
+
+    std::fs::create_dir_all(dir)?;
+
+    let mut paths = vec![];
+    for entry in &library.bookmarks {
+        let path = bookmark_path(dir, &entry.name, "hlbm");
+        entry
+            .bookmark
+            .write_to_file(&path.to_string_lossy())
+            .map_err(|err| format!("{}: {err}", entry.name))?;
+
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_exports_and_imports() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let from = dir.join("from");
+        std::fs::create_dir_all(&from).unwrap();
+
+        let mut bookmark = Bookmark::new("hotline.example.com".into());
+        bookmark
+            .credentials("guest".into(), "secret".into())
+            .unwrap();
+        bookmark
+            .write_apple_double(&from.join("Example.hlbm").to_string_lossy())
+            .unwrap();
+        Bookmark::new("other.example.com:5600".into())
+            .write_macbinary(&from.join("Other").to_string_lossy())
+            .unwrap();
+        std::fs::write(from.join("notes.txt"), "not a bookmark").unwrap();
+
+        let library = export(&from, false).unwrap();
+        let names: Vec<&str> = library.bookmarks.iter().map(|e| &e.name[..]).collect();
+        assert_eq!(names, vec!["Example", "Other"]);
+
+        for format in [LibraryFormat::Json, LibraryFormat::Toml] {
+            let text = format.to_string(&library).unwrap();
+            let library = format.parse(&text).unwrap();
+
+            let to = dir.join(format!("{format:?}"));
+            let paths = import(&library, &to).unwrap();
+            assert_eq!(paths, vec![to.join("Example.hlbm"), to.join("Other.hlbm")]);
+
+            let read = Bookmark::from_file(&paths[0].to_string_lossy()).unwrap();
+            assert_eq!(read.address, "hotline.example.com");
+            assert_eq!(read.password, "secret");
+        }
+
+        // a bookmark needs a version it can be written as
+        let library = LibraryFormat::Json
+            .parse(
+                r#"{"bookmarks": [
+                    {"name": "Good", "address": "hotline.example.com"},
+                    {"name": "Bad", "address": "hotline.example.com", "version": 0}
+                ]}"#,
+            )
+            .unwrap();
+        let to = dir.join("unversioned");
+        let err = import(&library, &to).unwrap_err();
+        assert_eq!(err.to_string(), "Bad: Unsupported bookmark version 0");
+        assert!(!to.exists());
+
+        let redacted = export(&from, true).unwrap();
+        assert_eq!(redacted.bookmarks[0].bookmark.username, "guest");
+        assert_eq!(redacted.bookmarks[0].bookmark.password, "");
+    }
+}
@@ -0,0 +1,109 @@
+use std::io::Read;
+
+use hotline_bookmark::bookmark::Bookmark;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+/// something to make a bookmark from: either a bookmark, like `hlbm print --json` prints, or a
+/// server, like `hotline-tracker-client list --format json` prints
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Record {
+    Server {
+        name: String,
+        address: String,
+        port: u16,
+    },
+    Bookmark(Bookmark),
+}
+
+/// a bookmark read from JSON, with a name to give its file
+#[derive(Debug)]
+pub struct NamedBookmark {
+    pub name: String,
+    pub bookmark: Bookmark,
+}
+
+impl From<Record> for NamedBookmark {
+    fn from(record: Record) -> Self {
+        match record {
+            Record::Server {
+                name,
+                address,
+                port,
+            } => Self {
+                name,
+                bookmark: Bookmark::new(format!("{address}:{port}")),
+            },
+            Record::Bookmark(bookmark) => Self {
+                name: bookmark.address.clone(),
+                bookmark,
+            },
+        }
+    }
+}
+
+/// read bookmarks from JSON. Records can be on their own, in arrays, or one per line.
+pub fn read_bookmarks<R: Read>(
+    reader: R,
+) -> Result<Vec<NamedBookmark>, Box<dyn std::error::Error>> {
+    let mut values = vec![];
+    for value in serde_json::Deserializer::from_reader(reader).into_iter::<Value>() {
+        match value? {
+            Value::Array(array) => values.extend(array),
+            value => values.push(value),
+        }
+    }
+
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(i, value)| {
+            serde_json::from_value::<Record>(value)
+                .map(NamedBookmark::from)
+                .map_err(|_| {
+                    format!(
+                        "record {} isn't a bookmark or a server from a tracker",
+                        i + 1
+                    )
+                    .into()
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_bookmarks_and_servers() {
+        let json = r#"
+            {"address": "hotline.example.com", "username": "guest"}
+            [
+                {"trackers": ["hltracker.com"], "address": "10.0.0.1", "port": 5500,
+                 "users_online": 3, "reserved": 0, "name": "My Server", "description": ""},
+                {"address": "other.example.com:5600", "username": "", "password": "pw"}
+            ]
+        "#;
+
+        let bookmarks = read_bookmarks(json.as_bytes()).unwrap();
+        assert_eq!(bookmarks.len(), 3);
+
+        assert_eq!(bookmarks[0].name, "hotline.example.com");
+        assert_eq!(bookmarks[0].bookmark.username, "guest");
+        assert_eq!(bookmarks[0].bookmark.password, "");
+
+        assert_eq!(bookmarks[1].name, "My Server");
+        assert_eq!(bookmarks[1].bookmark.address, "10.0.0.1:5500");
+
+        assert_eq!(bookmarks[2].bookmark.password, "pw");
+
+        let err = read_bookmarks(r#"{"address": "a"} {"name": "no address"}"#.as_bytes());
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "record 2 isn't a bookmark or a server from a tracker"
+        );
+    }
+}
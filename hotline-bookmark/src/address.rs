@@ -0,0 +1,215 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv6Addr};
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::bookmark::Bookmark;
+
+/// the port Hotline servers listen on when a bookmark doesn't say
+pub const DEFAULT_SERVER_PORT: u16 = 5500;
+
+/// the longest a hostname can be, leaving off any trailing dot
+const MAX_HOSTNAME_LEN: usize = 253;
+
+/// the longest each part of a hostname can be
+const MAX_LABEL_LEN: usize = 63;
+
+#[derive(Debug, Error)]
+#[error("Invalid server address {address:?}: {reason}")]
+pub struct InvalidAddress {
+    address: String,
+    reason: &'static str,
+}
+
+/// a bookmark's address split into its parts: `host`, `host:port`, `ipv6`, or `[ipv6]:port`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerAddress {
+    /// a hostname or IP address, without brackets
+    pub host: String,
+
+    /// the port from the address, or [`DEFAULT_SERVER_PORT`]
+    pub port: u16,
+}
+
+impl ServerAddress {
+    /// the host as an IP address, if it is one
+    pub fn ip(&self) -> Option<IpAddr> {
+        self.host.parse().ok()
+    }
+}
+
+/// whether `host` is a valid hostname: dot-separated labels of letters, digits and hyphens, with
+/// no label starting or ending with a hyphen
+fn is_valid_hostname(host: &str) -> bool {
+    let host = host.strip_suffix('.').unwrap_or(host);
+
+    !host.is_empty()
+        && host.len() <= MAX_HOSTNAME_LEN
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= MAX_LABEL_LEN
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        })
+}
+
+impl FromStr for ServerAddress {
+    type Err = InvalidAddress;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason| InvalidAddress {
+            address: s.into(),
+            reason,
+        };
+        let parse_port = |port: &str| {
+            port.parse::<u16>()
+                .ok()
+                .filter(|&port| port != 0)
+                .ok_or_else(|| invalid("the port isn't a number from 1 to 65535"))
+        };
+
+        let (host, port) = if let Some(rest) = s.strip_prefix('[') {
+            // [ipv6] or [ipv6]:port
+            let (host, rest) = rest
+                .split_once(']')
+                .ok_or_else(|| invalid("there's no closing bracket"))?;
+            host.parse::<Ipv6Addr>()
+                .map_err(|_| invalid("only IPv6 addresses go in brackets"))?;
+
+            let port = match rest {
+                "" => None,
+                rest => Some(parse_port(rest.strip_prefix(':').ok_or_else(|| {
+                    invalid("there's something after the closing bracket")
+                })?)?),
+            };
+
+            (host, port)
+        } else if s.parse::<Ipv6Addr>().is_ok() {
+            // a bare IPv6 address can't have a port
+            (s, None)
+        } else {
+            let (host, port) = match s.rsplit_once(':') {
+                Some((host, port)) => (host, Some(parse_port(port)?)),
+                None => (s, None),
+            };
+
+            // everything but IPv6 addresses has to be a hostname, which IPv4 addresses are too
+            if host.is_empty() {
+                return Err(invalid("there's no host"));
+            }
+            if !is_valid_hostname(host) {
+                return Err(invalid("the host isn't a valid hostname or IP address"));
+            }
+
+            (host, port)
+        };
+
+        Ok(Self {
+            host: host.into(),
+            port: port.unwrap_or(DEFAULT_SERVER_PORT),
+        })
+    }
+}
+
+impl fmt::Display for ServerAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+impl Bookmark {
+    /// the bookmark's address, parsed into a host and port
+    pub fn server_address(&self) -> Result<ServerAddress, InvalidAddress> {
+        self.address.trim().parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> ServerAddress {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn it_parses_server_addresses() {
+        assert_eq!(
+            parse("hotline.example.com"),
+            ServerAddress {
+                host: "hotline.example.com".into(),
+                port: DEFAULT_SERVER_PORT
+            }
+        );
+        assert_eq!(parse("hotline.example.com:5600").port, 5600);
+        assert_eq!(parse("hotline.example.com.").host, "hotline.example.com.");
+        assert_eq!(
+            parse("192.0.2.1:5500").ip(),
+            Some("192.0.2.1".parse().unwrap())
+        );
+        assert_eq!(parse("localhost").ip(), None);
+        assert_eq!(parse("2001:db8::1").host, "2001:db8::1");
+        assert_eq!(parse("2001:db8::1").port, DEFAULT_SERVER_PORT);
+        assert_eq!(
+            parse("[2001:db8::1]:5600"),
+            ServerAddress {
+                host: "2001:db8::1".into(),
+                port: 5600
+            }
+        );
+
+        for bad in [
+            "",
+            ":5500",
+            "hotline.example.com:",
+            "hotline.example.com:0",
+            "hotline.example.com:99999",
+            "hotline example.com",
+            "hotline..example.com",
+            "-hotline.example.com",
+            "hotline_example.com",
+            "[hotline.example.com]:5500",
+            "[2001:db8::1]5500",
+            "[2001:db8::1",
+            "foo:bar:5500",
+        ] {
+            assert!(bad.parse::<ServerAddress>().is_err(), "{bad:?} parsed");
+        }
+
+        let long = format!("{}.com", "a".repeat(64));
+        assert!(long.parse::<ServerAddress>().is_err());
+    }
+
+    #[test]
+    fn it_displays_server_addresses() {
+        assert_eq!(
+            parse("hotline.example.com").to_string(),
+            "hotline.example.com:5500"
+        );
+        assert_eq!(parse("2001:db8::1").to_string(), "[2001:db8::1]:5500");
+    }
+
+    #[test]
+    fn it_parses_bookmark_addresses() {
+        let bookmark = Bookmark::new(" hotline.example.com:5600 ".into());
+        let address = bookmark.server_address().unwrap();
+        assert_eq!(address.host, "hotline.example.com");
+        assert_eq!(address.port, 5600);
+
+        let err = Bookmark::new("not a host".into())
+            .server_address()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid server address \"not a host\": the host isn't a valid hostname or IP address"
+        );
+    }
+}
@@ -1,10 +1,14 @@
-use bytes::{Buf, BufMut, Bytes, BytesMut};
-use macroman_tools::macroman_to_string;
+use bytes::BytesMut;
+use macroman_tools::{macroman_to_string, try_string_to_macroman};
 
 use std::fs::File;
 use std::io::prelude::*;
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::container::{self, Container};
 
 const BOOKMARK_MAGIC_WORD: &[u8; 4] = b"HTsc";
 const BOOKMARK_VERSION: u16 = 1;
@@ -18,17 +22,178 @@ const USERNAME_OFFSET: usize = 135;
 const PASSWORD_OFFSET: usize = 169;
 const ADDRESS_OFFSET: usize = 203;
 
-/// a version 1 hotline bookmark
-#[derive(Debug, Serialize, Deserialize)]
+// field lengths are in MacRoman bytes, which is one per character
+
+/// the longest username a bookmark can hold, in bytes
+pub const MAX_USERNAME_LEN: usize = 31;
+
+/// the longest password a bookmark can hold, in bytes
+pub const MAX_PASSWORD_LEN: usize = 31;
+
+/// the longest address a bookmark can hold, in bytes
+pub const MAX_ADDRESS_LEN: usize = 255;
+
+#[derive(Debug, Error)]
+pub enum BookmarkError {
+    #[error("Not a bookmark file: it's {0} bytes long, but bookmarks are {BOOKMARK_LENGTH} bytes")]
+    TooShort(usize),
+
+    #[error("Not a bookmark file (expected \"HTsc\", got {0:?})")]
+    InvalidMagicWord([u8; 4]),
+
+    #[error("Unsupported bookmark version {0}")]
+    UnsupportedVersion(u16),
+
+    #[error("The {field} is {len} bytes long, but can be at most {max} bytes")]
+    FieldTooLong {
+        field: &'static str,
+        len: usize,
+        max: usize,
+    },
+
+    #[error("The {field} has {character:?}, which can't be written in MacRoman")]
+    Unrepresentable {
+        field: &'static str,
+        character: char,
+    },
+
+    #[error("Couldn't read the {0} file: {1}")]
+    InvalidContainer(Container, &'static str),
+
+    #[error("Not a hotline:// URL: {0}")]
+    InvalidUrl(String),
+
+    #[error("Can't write {0} files")]
+    UnsupportedContainer(Container),
+
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// convert a value to MacRoman, making sure it fits in its field
+pub(crate) fn encode_field(
+    field: &'static str,
+    value: &str,
+    max: usize,
+) -> Result<Vec<u8>, BookmarkError> {
+    let encoded = try_string_to_macroman(value)
+        .map_err(|character| BookmarkError::Unrepresentable { field, character })?;
+
+    if encoded.len() > max {
+        return Err(BookmarkError::FieldTooLong {
+            field,
+            len: encoded.len(),
+            max,
+        });
+    }
+
+    Ok(encoded)
+}
+
+/// write a length-prefixed value at `offset`, padded with zeros up to `end`
+fn put_field(buf: &mut [u8], value: &[u8], offset: usize, end: usize) {
+    let field = &mut buf[offset..end];
+    field.fill(0);
+    field[0] = value.len() as u8;
+    field[1..=value.len()].copy_from_slice(value);
+}
+
+/// write a file by writing a temporary file next to it and renaming that over it, so a failed write
+/// never leaves a half-written file behind
+fn write_atomically(path: &Path, data: &[u8]) -> Result<(), std::io::Error> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp_path = path.with_file_name(format!(".{name}.tmp"));
+
+    let result = File::create(&tmp_path).and_then(|mut f| {
+        // keep the permissions of the file being replaced, since bookmarks can have passwords in
+        if let Ok(metadata) = std::fs::metadata(path) {
+            f.set_permissions(metadata.permissions())?;
+        }
+
+        f.write_all(data)?;
+        f.sync_all()
+    });
+
+    if let Err(err) = result.and_then(|_| std::fs::rename(&tmp_path, path)) {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+/// pick a file name for a bookmark in `dir` based on the server's name, without overwriting
+/// existing files
+pub fn bookmark_path(dir: &Path, name: &str, extension: &str) -> PathBuf {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let name = name.trim().trim_start_matches('.');
+    let name = if name.is_empty() { "bookmark" } else { name };
+
+    let mut path = dir.join(format!("{name}.{extension}"));
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{name} {n}.{extension}"));
+        n += 1;
+    }
+
+    path
+}
+
+fn default_version() -> u16 {
+    BOOKMARK_VERSION
+}
+
+/// read the length-prefixed string at `offset`, which can be at most `max` bytes
+fn read_field(
+    data: &[u8],
+    field: &'static str,
+    offset: usize,
+    max: usize,
+) -> Result<String, BookmarkError> {
+    let len = data[offset] as usize;
+    if len > max {
+        return Err(BookmarkError::FieldTooLong { field, len, max });
+    }
+
+    Ok(macroman_to_string(&data[offset + 1..offset + 1 + len]))
+}
+
+/// a hotline bookmark. Only the version 1 fields are decoded. Later versions are read with the
+/// same layout, and whatever they add (server name, login icon, flags) isn't decoded: it's kept
+/// as unknown bytes and written back untouched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Bookmark {
-    /// the address for the bookmark. hostname or IP. may include port
+    /// the address for the bookmark. hostname or IP. may include port. See
+    /// [`Bookmark::server_address`] for its parts.
     pub address: String,
 
     /// the username credential. Can be blank.
+    #[serde(default)]
     pub username: String,
 
     /// the password credential. can be blank.
+    #[serde(default)]
     pub password: String,
+
+    /// the version of the bookmark format
+    #[serde(default = "default_version")]
+    pub version: u16,
+
+    /// the file this was read from, kept as opaque bytes. Clients keep their own data between
+    /// the header and the username, and later versions add more after the address, so writing
+    /// the bookmark back starts from this and only replaces the fields above.
+    #[serde(skip)]
+    raw: Vec<u8>,
+
+    #[serde(skip)]
+    container: Container,
 }
 
 impl Bookmark {
@@ -37,79 +202,452 @@ impl Bookmark {
             address,
             username: "".into(),
             password: "".into(),
+            version: BOOKMARK_VERSION,
+            raw: vec![],
+            container: Container::Raw,
         }
     }
 
-    pub fn credentials(&mut self, username: String, password: String) -> &mut Self {
-        // TODO: validate length. max len of these is 33.
+    /// set the username and password, which can each be at most 31 MacRoman characters
+    pub fn credentials(
+        &mut self,
+        username: String,
+        password: String,
+    ) -> Result<&mut Self, BookmarkError> {
+        encode_field("username", &username, MAX_USERNAME_LEN)?;
+        encode_field("password", &password, MAX_PASSWORD_LEN)?;
+
         self.username = username;
         self.password = password;
 
-        self
+        Ok(self)
     }
 
-    pub fn to_bytes(&self) -> BytesMut {
-        let mut buf = BytesMut::with_capacity(BOOKMARK_LENGTH);
-
-        // header
-        buf.put_slice(BOOKMARK_MAGIC_WORD);
-        buf.put_u16(BOOKMARK_VERSION);
+    pub fn to_bytes(&self) -> Result<BytesMut, BookmarkError> {
+        if self.version < BOOKMARK_VERSION {
+            return Err(BookmarkError::UnsupportedVersion(self.version));
+        }
 
-        // zero padding
-        buf.put_bytes(0, USERNAME_OFFSET - BOOKMARK_HEADER_LEN);
+        let username = encode_field("username", &self.username, MAX_USERNAME_LEN)?;
+        let password = encode_field("password", &self.password, MAX_PASSWORD_LEN)?;
+        let address = encode_field("address", &self.address, MAX_ADDRESS_LEN)?;
 
-        // username
-        buf.put_u8(self.username.len() as u8);
-        buf.put_slice(self.username.as_bytes());
-        buf.put_bytes(0, 33 - self.username.len());
+        // a new bookmark is all zeros apart from the header and fields
+        let mut buf = if self.raw.is_empty() {
+            BytesMut::zeroed(BOOKMARK_LENGTH)
+        } else {
+            BytesMut::from(&self.raw[..])
+        };
 
-        // password
-        buf.put_u8(self.password.len() as u8);
-        buf.put_slice(self.password.as_bytes());
-        buf.put_bytes(0, 33 - self.password.len());
+        // header
+        buf[..4].copy_from_slice(BOOKMARK_MAGIC_WORD);
+        buf[4..BOOKMARK_HEADER_LEN].copy_from_slice(&self.version.to_be_bytes());
 
-        // address
-        buf.put_u8(self.address.len() as u8);
-        buf.put_slice(self.address.as_bytes());
-        buf.put_bytes(0, 256 - self.address.len());
+        put_field(&mut buf, &username, USERNAME_OFFSET, PASSWORD_OFFSET);
+        put_field(&mut buf, &password, PASSWORD_OFFSET, ADDRESS_OFFSET);
+        put_field(&mut buf, &address, ADDRESS_OFFSET, BOOKMARK_LENGTH);
 
-        buf
+        Ok(buf)
     }
 
-    pub fn from_file(path: &str) -> Result<Self, std::io::Error> {
-        let data = std::fs::read(path)?;
-        let mut buf = Bytes::from(data);
-
-        // read header
-        let magic_word: Vec<u8> = buf.copy_to_bytes(BOOKMARK_MAGIC_WORD.len()).to_vec();
-        let version = buf.get_u16();
+    /// parse a bookmark file's contents. Bookmarks wrapped in MacBinary, AppleSingle or BinHex are
+    /// unwrapped first.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, BookmarkError> {
+        let (container, data) = container::data_fork(data)?;
+        let data = &data[..];
 
-        assert_eq!(magic_word, BOOKMARK_MAGIC_WORD);
-        assert_eq!(version, BOOKMARK_VERSION);
+        if data.len() < BOOKMARK_LENGTH {
+            return Err(BookmarkError::TooShort(data.len()));
+        }
 
-        buf.advance(USERNAME_OFFSET - BOOKMARK_HEADER_LEN);
-        let username_len = buf.get_u8() as usize;
-        let username: String = macroman_to_string(&buf.copy_to_bytes(username_len));
+        // read header
+        let magic_word: [u8; 4] = data[..4].try_into().unwrap();
+        let version = u16::from_be_bytes([data[4], data[5]]);
 
-        buf.advance(33 - username_len);
-        let password_len = buf.get_u8() as usize;
-        let password: String = macroman_to_string(&buf.copy_to_bytes(password_len));
+        if &magic_word != BOOKMARK_MAGIC_WORD {
+            return Err(BookmarkError::InvalidMagicWord(magic_word));
+        }
+        if version < BOOKMARK_VERSION {
+            return Err(BookmarkError::UnsupportedVersion(version));
+        }
 
-        buf.advance(33 - password_len);
-        let address_len = buf.get_u8() as usize;
-        let address: String = macroman_to_string(&buf.copy_to_bytes(address_len));
+        // each value can fill its field, up to where the next one starts. Writing is limited to
+        // what Hotline itself allows, so a bookmark with a longer value needs it shortened to be
+        // saved again.
+        let username = read_field(
+            data,
+            "username",
+            USERNAME_OFFSET,
+            PASSWORD_OFFSET - USERNAME_OFFSET - 1,
+        )?;
+        let password = read_field(
+            data,
+            "password",
+            PASSWORD_OFFSET,
+            ADDRESS_OFFSET - PASSWORD_OFFSET - 1,
+        )?;
+        let address = read_field(data, "address", ADDRESS_OFFSET, MAX_ADDRESS_LEN)?;
 
         Ok(Self {
             username,
             password,
             address,
+            version,
+            raw: data.to_vec(),
+            container,
         })
     }
 
-    pub fn write_to_file(&self, path: &str) -> Result<usize, std::io::Error> {
-        let mut f = File::create(path)?;
-        let buf = self.to_bytes();
+    /// read a bookmark file from `reader`
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, BookmarkError> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+
+        Self::from_bytes(&data)
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, BookmarkError> {
+        Self::from_reader(File::open(path)?)
+    }
+
+    /// how the file this bookmark was read from was stored
+    pub fn container(&self) -> Container {
+        self.container
+    }
+
+    pub fn write_to_file(&self, path: &str) -> Result<usize, BookmarkError> {
+        let buf = self.to_bytes()?;
+        write_atomically(Path::new(path), &buf)?;
+
+        Ok(buf.len())
+    }
+
+    /// write the bookmark back the way it was stored when it was read
+    pub fn save(&self, path: &str) -> Result<usize, BookmarkError> {
+        match self.container {
+            Container::Raw => self.write_to_file(path),
+            Container::MacBinary => self.write_macbinary(path),
+            container => Err(BookmarkError::UnsupportedContainer(container)),
+        }
+    }
+
+    /// write the bookmark wrapped in MacBinary, so it keeps its type and creator when it's copied
+    /// to a Mac. The file's name on the Mac is `path`'s, without any `.bin` extension.
+    pub fn write_macbinary(&self, path: &str) -> Result<usize, BookmarkError> {
+        let path = Path::new(path);
+        let name = match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("bin") => path.file_stem(),
+            _ => path.file_name(),
+        };
+        let name = name.map(|n| n.to_string_lossy()).unwrap_or_default();
+
+        let buf = container::to_macbinary(&name, &self.to_bytes()?);
+        write_atomically(path, &buf)?;
+
+        Ok(buf.len())
+    }
+
+    /// write the bookmark to `path`, and its type and creator to an AppleDouble file next to it
+    /// named `._` and the bookmark's name, which is how macOS stores them on other filesystems.
+    pub fn write_apple_double(&self, path: &str) -> Result<usize, BookmarkError> {
+        let written = self.write_to_file(path)?;
+
+        let path = Path::new(path);
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        write_atomically(
+            &path.with_file_name(format!("._{name}")),
+            &container::to_apple_double(),
+        )?;
+
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_round_trips() {
+        let mut bookmark = Bookmark::new("hotline.example.com:5500".into());
+        bookmark
+            .credentials("guest".into(), "secret".into())
+            .unwrap();
+
+        let bytes = bookmark.to_bytes().unwrap();
+        assert_eq!(bytes.len(), BOOKMARK_LENGTH);
+        assert_eq!(&bytes[..6], b"HTsc\0\x01");
+
+        let read = Bookmark::from_reader(&bytes[..]).unwrap();
+        assert_eq!(read.address, "hotline.example.com:5500");
+        assert_eq!(read.username, "guest");
+        assert_eq!(read.password, "secret");
+    }
+
+    #[test]
+    fn it_writes_macroman() {
+        let mut bookmark = Bookmark::new("caf\u{e9}.example.com".into());
+        bookmark
+            .credentials("\u{c5}sa".into(), "\u{3c0}\u{2260}3".into())
+            .unwrap();
+
+        let bytes = bookmark.to_bytes().unwrap();
+        assert_eq!(&bytes[USERNAME_OFFSET..USERNAME_OFFSET + 4], b"\x03\x81sa");
+        assert_eq!(
+            &bytes[PASSWORD_OFFSET..PASSWORD_OFFSET + 4],
+            b"\x03\xb9\xad3"
+        );
+        assert_eq!(&bytes[ADDRESS_OFFSET..ADDRESS_OFFSET + 5], b"\x10caf\x8e");
+
+        // 31 characters is 31 bytes, even where UTF-8 would need more
+        assert!(bookmark.credentials("\u{e9}".repeat(31), "".into()).is_ok());
+
+        assert!(matches!(
+            bookmark.credentials("\u{1f600}".into(), "".into()),
+            Err(BookmarkError::Unrepresentable {
+                field: "username",
+                character: '\u{1f600}'
+            })
+        ));
+    }
+
+    #[test]
+    fn it_round_trips_every_macroman_character() {
+        let all: Vec<u8> = (0..=255).collect();
+
+        for chunk in all.chunks(MAX_USERNAME_LEN) {
+            let mut bookmark = Bookmark::new(macroman_to_string(&all[1..]));
+            bookmark.username = macroman_to_string(chunk);
+            bookmark.password = macroman_to_string(chunk).chars().rev().collect();
+
+            let bytes = bookmark.to_bytes().unwrap();
+            assert_eq!(
+                &bytes[USERNAME_OFFSET + 1..USERNAME_OFFSET + 1 + chunk.len()],
+                chunk
+            );
+            assert_eq!(&bytes[ADDRESS_OFFSET + 1..BOOKMARK_LENGTH - 1], &all[1..]);
+
+            let read = Bookmark::from_bytes(&bytes).unwrap();
+            assert_eq!(read.username, bookmark.username);
+            assert_eq!(read.password, bookmark.password);
+            assert_eq!(read.address, bookmark.address);
+        }
+    }
+
+    #[test]
+    fn it_preserves_unknown_data() {
+        let mut original = Bookmark::new("hotline.example.com".into());
+        original
+            .credentials("guest".into(), "a long password".into())
+            .unwrap();
+        let mut bytes = original.to_bytes().unwrap().to_vec();
+
+        // a newer version, with client data in the reserved space and bytes we don't decode on
+        // the end
+        bytes[5] = 3;
+        bytes[BOOKMARK_HEADER_LEN..USERNAME_OFFSET].fill(0xab);
+        bytes.extend_from_slice(&[0x0b, 0xde, 0xad, 0xbe, 0xef, 0x00, 0x80]);
+
+        let mut bookmark = Bookmark::from_bytes(&bytes).unwrap();
+        assert_eq!(bookmark.version, 3);
+        assert_eq!(bookmark.password, "a long password");
+
+        bookmark.password = "short".into();
+        let written = bookmark.to_bytes().unwrap();
+
+        assert_eq!(written.len(), bytes.len());
+        assert_eq!(&written[..PASSWORD_OFFSET], &bytes[..PASSWORD_OFFSET]);
+        assert_eq!(&written[ADDRESS_OFFSET..], &bytes[ADDRESS_OFFSET..]);
+
+        // the old password doesn't linger after the new one
+        let password = &written[PASSWORD_OFFSET..ADDRESS_OFFSET];
+        assert_eq!(&password[..6], b"\x05short");
+        assert!(password[6..].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn it_reads_and_writes_containers() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+
+        let mut bookmark = Bookmark::new("hotline.example.com".into());
+        bookmark.credentials("guest".into(), "".into()).unwrap();
+
+        let path = dir.join("Example.bin");
+        bookmark.write_macbinary(&path.to_string_lossy()).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(&data[1..9], b"\x07Example");
+
+        let mut read = Bookmark::from_file(&path.to_string_lossy()).unwrap();
+        assert_eq!(read.address, "hotline.example.com");
+        assert_eq!(read.username, "guest");
+        assert_eq!(read.container(), Container::MacBinary);
+
+        // saving keeps it in MacBinary
+        read.username = "admin".into();
+        read.save(&path.to_string_lossy()).unwrap();
+        let read = Bookmark::from_file(&path.to_string_lossy()).unwrap();
+        assert_eq!(read.username, "admin");
+        assert_eq!(read.container(), Container::MacBinary);
+
+        let path = dir.join("Example");
+        bookmark
+            .write_apple_double(&path.to_string_lossy())
+            .unwrap();
+        let read = Bookmark::from_file(&path.to_string_lossy()).unwrap();
+        assert_eq!(read.address, "hotline.example.com");
+
+        // the AppleDouble file on its own has no bookmark in it
+        assert!(matches!(
+            Bookmark::from_file(&dir.join("._Example").to_string_lossy()),
+            Err(BookmarkError::InvalidContainer(Container::AppleDouble, _))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_replaces_files_atomically() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let path = dir.join("Private");
+        let path_str = path.to_string_lossy();
+
+        let mut bookmark = Bookmark::new("hotline.example.com".into());
+        bookmark.write_to_file(&path_str).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+        bookmark.password = "secret".into();
+        bookmark.write_to_file(&path_str).unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(std::fs::read_dir(dir).unwrap().count(), 1);
+
+        // a bookmark that can't be written leaves the old one alone
+        bookmark.password = "p".repeat(40);
+        assert!(bookmark.write_to_file(&path_str).is_err());
+        assert_eq!(Bookmark::from_file(&path_str).unwrap().password, "secret");
+    }
+
+    #[test]
+    fn it_picks_bookmark_file_names() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+
+        let path = bookmark_path(dir, "My/Server: Mac", "hlbm");
+        assert_eq!(path, dir.join("My_Server_ Mac.hlbm"));
+
+        std::fs::write(&path, b"").unwrap();
+        assert_eq!(
+            bookmark_path(dir, "My/Server: Mac", "hlbm"),
+            dir.join("My_Server_ Mac 2.hlbm")
+        );
+        assert_eq!(bookmark_path(dir, "../..", "bin"), dir.join("_...bin"));
+        assert_eq!(bookmark_path(dir, " ", "bin"), dir.join("bookmark.bin"));
+    }
+
+    #[test]
+    fn it_rejects_long_fields() {
+        let mut bookmark = Bookmark::new("hotline.example.com".into());
+        assert!(matches!(
+            bookmark.credentials("u".repeat(32), "".into()),
+            Err(BookmarkError::FieldTooLong {
+                field: "username",
+                len: 32,
+                max: 31
+            })
+        ));
+        assert!(bookmark.credentials("u".repeat(31), "p".repeat(31)).is_ok());
+
+        // the fields are public, so writing checks them too
+        bookmark.password = "p".repeat(40);
+        assert!(matches!(
+            bookmark.to_bytes(),
+            Err(BookmarkError::FieldTooLong {
+                field: "password",
+                ..
+            })
+        ));
+
+        bookmark.password = "".into();
+        bookmark.address = "a".repeat(256);
+        assert!(matches!(
+            bookmark.to_bytes(),
+            Err(BookmarkError::FieldTooLong {
+                field: "address",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn it_rejects_bad_files() {
+        let good = Bookmark::new("hotline.example.com".into())
+            .to_bytes()
+            .unwrap();
+
+        assert!(matches!(
+            Bookmark::from_bytes(&good[..100]),
+            Err(BookmarkError::TooShort(100))
+        ));
+        assert!(matches!(
+            Bookmark::from_bytes(b""),
+            Err(BookmarkError::TooShort(0))
+        ));
+
+        let mut bad = good.clone();
+        bad[..4].copy_from_slice(b"JUNK");
+        assert!(matches!(
+            Bookmark::from_bytes(&bad),
+            Err(BookmarkError::InvalidMagicWord(magic)) if &magic == b"JUNK"
+        ));
+
+        let mut bad = good.clone();
+        bad[5] = 0;
+        assert!(matches!(
+            Bookmark::from_bytes(&bad),
+            Err(BookmarkError::UnsupportedVersion(0))
+        ));
+
+        let mut unversioned = Bookmark::new("hotline.example.com".into());
+        unversioned.version = 0;
+        assert!(matches!(
+            unversioned.to_bytes(),
+            Err(BookmarkError::UnsupportedVersion(0))
+        ));
+
+        // a username length that would run into the password
+        let mut bad = good.clone();
+        bad[USERNAME_OFFSET] = 200;
+        assert!(matches!(
+            Bookmark::from_bytes(&bad),
+            Err(BookmarkError::FieldTooLong {
+                field: "username",
+                len: 200,
+                ..
+            })
+        ));
+
+        // the field has room for 33 bytes, so longer passwords read fine, but only 31 can be
+        // written
+        let mut long = good.to_vec();
+        long[PASSWORD_OFFSET] = 32;
+        long[PASSWORD_OFFSET + 1..PASSWORD_OFFSET + 33].fill(b'p');
+        let mut read = Bookmark::from_bytes(&long).unwrap();
+        assert_eq!(read.password, "p".repeat(32));
+        assert!(matches!(
+            read.to_bytes(),
+            Err(BookmarkError::FieldTooLong {
+                field: "password",
+                len: 32,
+                max: MAX_PASSWORD_LEN
+            })
+        ));
 
-        f.write(&buf)
+        // so it can be fixed by changing it
+        read.credentials("guest".into(), "short".into()).unwrap();
+        assert!(read.to_bytes().is_ok());
     }
 }
@@ -0,0 +1,516 @@
+//! Classic Mac files have a data fork, a resource fork and Finder info (type and creator). When
+//! they're copied off a Mac they're often wrapped up in one of these formats to keep all three.
+//! Bookmarks only use the data fork, so that's all we take out of them.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use macroman_tools::string_to_macroman;
+
+use crate::bookmark::BookmarkError;
+
+/// a bookmark's Finder type
+pub const BOOKMARK_TYPE: &[u8; 4] = b"HTsc";
+
+/// a bookmark's Finder creator, the Hotline client
+pub const BOOKMARK_CREATOR: &[u8; 4] = b"HTLC";
+
+const MACBINARY_HEADER_LEN: usize = 128;
+const MACBINARY_SIGNATURE: &[u8; 4] = b"mBIN";
+const MACBINARY_III_VERSION: u8 = 130;
+const MACBINARY_II_VERSION: u8 = 129;
+
+const APPLE_SINGLE_MAGIC: u32 = 0x0005_1600;
+const APPLE_DOUBLE_MAGIC: u32 = 0x0005_1607;
+const APPLE_DOUBLE_VERSION: u32 = 0x0002_0000;
+const APPLE_DOUBLE_HEADER_LEN: usize = 26;
+const APPLE_DOUBLE_ENTRY_LEN: usize = 12;
+const DATA_FORK_ENTRY: u32 = 1;
+const FINDER_INFO_ENTRY: u32 = 9;
+const FINDER_INFO_LEN: usize = 32;
+
+const BINHEX_BANNER: &str = "(This file must be converted with BinHex";
+const BINHEX_ALPHABET: &[u8; 64] =
+    b"!\"#$%&'()*+,-012345689@ABCDEFGHIJKLMNPQRSTUVXYZ[`abcdefhijklmpqr";
+const BINHEX_RUN_MARKER: u8 = 0x90;
+
+// seconds between the classic Mac epoch (1904) and the unix one
+const MAC_EPOCH_OFFSET: u64 = 2_082_844_800;
+
+/// how a bookmark file was stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Container {
+    /// just the data fork
+    #[default]
+    Raw,
+    MacBinary,
+    AppleSingle,
+    AppleDouble,
+    BinHex,
+}
+
+impl fmt::Display for Container {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Container::Raw => "raw",
+            Container::MacBinary => "MacBinary",
+            Container::AppleSingle => "AppleSingle",
+            Container::AppleDouble => "AppleDouble",
+            Container::BinHex => "BinHex",
+        };
+
+        f.write_str(name)
+    }
+}
+
+/// the data fork of a file that might be wrapped in a container, along with which container it
+/// was. Anything we don't recognise is assumed to be a raw data fork.
+pub fn data_fork(data: &[u8]) -> Result<(Container, Cow<'_, [u8]>), BookmarkError> {
+    if data.starts_with(BOOKMARK_TYPE) {
+        return Ok((Container::Raw, Cow::Borrowed(data)));
+    }
+
+    if let Some(magic) = read_u32(data, 0) {
+        if magic == APPLE_SINGLE_MAGIC || magic == APPLE_DOUBLE_MAGIC {
+            let container = if magic == APPLE_SINGLE_MAGIC {
+                Container::AppleSingle
+            } else {
+                Container::AppleDouble
+            };
+
+            return apple_double_data_fork(data)
+                .map(|fork| (container, Cow::Borrowed(fork)))
+                .map_err(|reason| BookmarkError::InvalidContainer(container, reason));
+        }
+    }
+
+    if is_macbinary(data) {
+        return macbinary_data_fork(data)
+            .map(|fork| (Container::MacBinary, Cow::Borrowed(fork)))
+            .map_err(|reason| BookmarkError::InvalidContainer(Container::MacBinary, reason));
+    }
+
+    if let Some(encoded) = find_binhex(data) {
+        return binhex_data_fork(encoded)
+            .map(|fork| (Container::BinHex, Cow::Owned(fork)))
+            .map_err(|reason| BookmarkError::InvalidContainer(Container::BinHex, reason));
+    }
+
+    Ok((Container::Raw, Cow::Borrowed(data)))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+/// CRC-16/XMODEM, which MacBinary II and BinHex both use
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+/// finder info for a bookmark: type and creator, then flags, location and folder left as zero
+fn finder_info() -> [u8; 16] {
+    let mut info = [0u8; 16];
+    info[..4].copy_from_slice(BOOKMARK_TYPE);
+    info[4..8].copy_from_slice(BOOKMARK_CREATOR);
+
+    info
+}
+
+/// MacBinary has no magic word, so this checks the header's fixed zero bytes and lengths instead.
+/// MacBinary II and III headers also have a CRC.
+fn is_macbinary(data: &[u8]) -> bool {
+    if data.len() < MACBINARY_HEADER_LEN {
+        return false;
+    }
+
+    let header = &data[..MACBINARY_HEADER_LEN];
+    let name_len = header[1] as usize;
+    if header[0] != 0 || header[74] != 0 || header[82] != 0 || !(1..=63).contains(&name_len) {
+        return false;
+    }
+
+    if &header[102..106] == MACBINARY_SIGNATURE {
+        return true;
+    }
+
+    let crc = read_u16(header, 124).unwrap();
+    if crc != 0 && crc == crc16(&header[..124]) {
+        return true;
+    }
+
+    // MacBinary I: no CRC, and the rest of the header is zero
+    header[99..].iter().all(|b| *b == 0)
+}
+
+fn macbinary_data_fork(data: &[u8]) -> Result<&[u8], &'static str> {
+    let data_len = read_u32(data, 83).unwrap() as usize;
+
+    // MacBinary II can have a secondary header, padded out to 128 bytes
+    let secondary_len = read_u16(data, 120).unwrap() as usize;
+    let start = MACBINARY_HEADER_LEN + secondary_len.div_ceil(128) * 128;
+
+    data.get(start..start + data_len)
+        .ok_or("the data fork is cut off")
+}
+
+/// AppleSingle files have every fork in them. AppleDouble files have everything but the data fork,
+/// which is in a file of its own, so there's usually nothing for us here.
+fn apple_double_data_fork(data: &[u8]) -> Result<&[u8], &'static str> {
+    let entry_count = read_u16(data, 24).ok_or("the header is cut off")? as usize;
+
+    for i in 0..entry_count {
+        let offset = APPLE_DOUBLE_HEADER_LEN + i * APPLE_DOUBLE_ENTRY_LEN;
+        let (id, start, len) = match (
+            read_u32(data, offset),
+            read_u32(data, offset + 4),
+            read_u32(data, offset + 8),
+        ) {
+            (Some(id), Some(start), Some(len)) => (id, start as usize, len as usize),
+            _ => return Err("the entries are cut off"),
+        };
+
+        if id == DATA_FORK_ENTRY {
+            return data
+                .get(start..start + len)
+                .ok_or("the data fork is cut off");
+        }
+    }
+
+    Err("there's no data fork. The bookmark is in the file this one goes with.")
+}
+
+/// the encoded part of a BinHex file, between the colons
+fn find_binhex(data: &[u8]) -> Option<&[u8]> {
+    let text = std::str::from_utf8(data).ok()?;
+
+    // the data starts with the first colon after the banner, or at the start of a line if there's
+    // no banner
+    let start = match text.find(BINHEX_BANNER) {
+        Some(banner) => banner + text[banner..].find(':')?,
+        None if text.starts_with(':') => 0,
+        None => text.find("\n:")? + 1,
+    };
+
+    let end = start + 1 + text[start + 1..].find(':')?;
+
+    Some(&data[start + 1..end])
+}
+
+fn binhex_data_fork(encoded: &[u8]) -> Result<Vec<u8>, &'static str> {
+    // six bits per character
+    let mut bits: u32 = 0;
+    let mut bit_count = 0;
+    let mut packed = vec![];
+    for c in encoded {
+        if c.is_ascii_whitespace() {
+            continue;
+        }
+
+        let value = BINHEX_ALPHABET
+            .iter()
+            .position(|a| a == c)
+            .ok_or("it has a character that isn't BinHex")?;
+
+        bits = (bits << 6) | value as u32;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            packed.push((bits >> bit_count) as u8);
+        }
+    }
+
+    // runs are stored as the byte, the marker, and how many times the byte appears
+    let mut decoded: Vec<u8> = Vec::with_capacity(packed.len());
+    let mut bytes = packed.into_iter();
+    while let Some(byte) = bytes.next() {
+        if byte != BINHEX_RUN_MARKER {
+            decoded.push(byte);
+            continue;
+        }
+
+        match bytes.next() {
+            // a literal marker byte
+            Some(0) => decoded.push(BINHEX_RUN_MARKER),
+            Some(count) => {
+                let last = *decoded.last().ok_or("it starts with a run")?;
+                decoded.extend(std::iter::repeat_n(last, count as usize - 1));
+            }
+            None => return Err("it ends partway through a run"),
+        }
+    }
+
+    // name, a version byte, type, creator, flags, then the fork lengths and a CRC of all that
+    let name_len = *decoded.first().ok_or("it's empty")? as usize;
+    let header_len = 1 + name_len + 1 + 4 + 4 + 2 + 4 + 4;
+    let data_len = read_u32(&decoded, header_len - 8).ok_or("the header is cut off")? as usize;
+    let header_crc = read_u16(&decoded, header_len).ok_or("the header is cut off")?;
+    if header_crc != crc16(&decoded[..header_len]) {
+        return Err("the header's CRC doesn't match");
+    }
+
+    let start = header_len + 2;
+    let data = decoded
+        .get(start..start + data_len)
+        .ok_or("the data fork is cut off")?;
+    let data_crc = read_u16(&decoded, start + data_len).ok_or("the data fork is cut off")?;
+    if data_crc != crc16(data) {
+        return Err("the data fork's CRC doesn't match");
+    }
+
+    Ok(data.to_vec())
+}
+
+fn mac_time_now() -> u32 {
+    let unix = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    (unix + MAC_EPOCH_OFFSET) as u32
+}
+
+/// wrap a bookmark in MacBinary III, which MacBinary II readers also understand. `name` is the
+/// file's name on the Mac.
+pub fn to_macbinary(name: &str, data: &[u8]) -> Vec<u8> {
+    let mut name = string_to_macroman(name);
+    name.truncate(63);
+
+    let mut header = [0u8; MACBINARY_HEADER_LEN];
+    header[1] = name.len() as u8;
+    header[2..2 + name.len()].copy_from_slice(&name);
+    header[65..73].copy_from_slice(&finder_info()[..8]);
+    header[83..87].copy_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let now = mac_time_now().to_be_bytes();
+    header[91..95].copy_from_slice(&now);
+    header[95..99].copy_from_slice(&now);
+
+    header[102..106].copy_from_slice(MACBINARY_SIGNATURE);
+    header[122] = MACBINARY_III_VERSION;
+    header[123] = MACBINARY_II_VERSION;
+    let crc = crc16(&header[..124]);
+    header[124..126].copy_from_slice(&crc.to_be_bytes());
+
+    // the data fork is padded out to a multiple of 128 bytes
+    let mut file = header.to_vec();
+    file.extend_from_slice(data);
+    file.resize(MACBINARY_HEADER_LEN + data.len().div_ceil(128) * 128, 0);
+
+    file
+}
+
+/// the AppleDouble file that goes alongside a bookmark, giving it its type and creator. By
+/// convention it's named like the bookmark with `._` in front.
+pub fn to_apple_double() -> Vec<u8> {
+    let mut file = vec![];
+    file.extend_from_slice(&APPLE_DOUBLE_MAGIC.to_be_bytes());
+    file.extend_from_slice(&APPLE_DOUBLE_VERSION.to_be_bytes());
+    file.extend_from_slice(&[0u8; 16]);
+
+    // one entry, the finder info, right after the entry table
+    file.extend_from_slice(&1u16.to_be_bytes());
+    file.extend_from_slice(&FINDER_INFO_ENTRY.to_be_bytes());
+    file.extend_from_slice(
+        &((APPLE_DOUBLE_HEADER_LEN + APPLE_DOUBLE_ENTRY_LEN) as u32).to_be_bytes(),
+    );
+    file.extend_from_slice(&(FINDER_INFO_LEN as u32).to_be_bytes());
+
+    let mut info = [0u8; FINDER_INFO_LEN];
+    info[..16].copy_from_slice(&finder_info());
+    file.extend_from_slice(&info);
+
+    file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark_bytes() -> Vec<u8> {
+        let mut data = b"HTsc\x00\x01".to_vec();
+        data.resize(460, 0);
+        data[203..209].copy_from_slice(b"\x05hello");
+
+        data
+    }
+
+    /// a BinHex encoder, with runs of three or more bytes compressed
+    fn to_binhex(name: &str, data: &[u8]) -> String {
+        let mut header = vec![name.len() as u8];
+        header.extend_from_slice(name.as_bytes());
+        header.push(0);
+        header.extend_from_slice(&finder_info()[..10]);
+        header.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        header.extend_from_slice(&0u32.to_be_bytes());
+
+        let mut decoded = header.clone();
+        decoded.extend_from_slice(&crc16(&header).to_be_bytes());
+        decoded.extend_from_slice(data);
+        decoded.extend_from_slice(&crc16(data).to_be_bytes());
+        decoded.extend_from_slice(&crc16(&[]).to_be_bytes());
+
+        let mut packed = vec![];
+        let mut i = 0;
+        while i < decoded.len() {
+            let byte = decoded[i];
+            let run = decoded[i..]
+                .iter()
+                .take(255)
+                .take_while(|b| **b == byte)
+                .count();
+
+            packed.push(byte);
+            if byte == BINHEX_RUN_MARKER {
+                packed.push(0);
+                i += 1;
+            } else if run >= 3 {
+                packed.extend_from_slice(&[BINHEX_RUN_MARKER, run as u8]);
+                i += run;
+            } else {
+                i += 1;
+            }
+        }
+
+        let mut text = format!("{BINHEX_BANNER} 4.0)\r\n:");
+        for (n, chunk) in packed.chunks(3).enumerate() {
+            let mut group = [0u8; 3];
+            group[..chunk.len()].copy_from_slice(chunk);
+            let bits = u32::from_be_bytes([0, group[0], group[1], group[2]]);
+
+            for j in 0..chunk.len() + 1 {
+                let value = (bits >> (18 - 6 * j)) & 0x3f;
+                text.push(BINHEX_ALPHABET[value as usize] as char);
+            }
+            if n % 16 == 15 {
+                text.push_str("\r\n");
+            }
+        }
+        text.push(':');
+
+        text
+    }
+
+    #[test]
+    fn it_reads_raw_files() {
+        let data = bookmark_bytes();
+        let (container, fork) = data_fork(&data).unwrap();
+
+        assert_eq!(container, Container::Raw);
+        assert_eq!(fork, &data[..]);
+    }
+
+    #[test]
+    fn it_round_trips_macbinary() {
+        let data = bookmark_bytes();
+        let file = to_macbinary("My Server", &data);
+
+        assert_eq!(file.len(), 128 + 512);
+        assert_eq!(&file[2..11], b"My Server");
+        assert_eq!(&file[65..73], b"HTscHTLC");
+
+        let (container, fork) = data_fork(&file).unwrap();
+        assert_eq!(container, Container::MacBinary);
+        assert_eq!(fork, &data[..]);
+
+        // a MacBinary II reader only checks the CRC
+        let mut mac_binary_ii = file.clone();
+        mac_binary_ii[102..106].fill(0);
+        let crc = crc16(&mac_binary_ii[..124]);
+        mac_binary_ii[124..126].copy_from_slice(&crc.to_be_bytes());
+        assert_eq!(data_fork(&mac_binary_ii).unwrap().0, Container::MacBinary);
+
+        // MacBinary I has neither
+        let mut mac_binary_i = file.clone();
+        mac_binary_i[99..128].fill(0);
+        assert_eq!(data_fork(&mac_binary_i).unwrap().0, Container::MacBinary);
+
+        let mut truncated = file;
+        truncated.truncate(300);
+        assert!(matches!(
+            data_fork(&truncated),
+            Err(BookmarkError::InvalidContainer(
+                Container::MacBinary,
+                "the data fork is cut off"
+            ))
+        ));
+    }
+
+    #[test]
+    fn it_reads_apple_single_and_double() {
+        let data = bookmark_bytes();
+
+        // an AppleSingle file is an AppleDouble one with the data fork added
+        let mut single = to_apple_double();
+        single[..4].copy_from_slice(&APPLE_SINGLE_MAGIC.to_be_bytes());
+        single[25] = 2;
+        single.splice(38..38, [0u8; 12]);
+        single[30..34].copy_from_slice(&50u32.to_be_bytes());
+        let start = single.len() as u32;
+        single[38..50].copy_from_slice(
+            &[
+                DATA_FORK_ENTRY.to_be_bytes(),
+                start.to_be_bytes(),
+                (data.len() as u32).to_be_bytes(),
+            ]
+            .concat(),
+        );
+        single.extend_from_slice(&data);
+
+        let (container, fork) = data_fork(&single).unwrap();
+        assert_eq!(container, Container::AppleSingle);
+        assert_eq!(fork, &data[..]);
+
+        let double = to_apple_double();
+        assert_eq!(&double[38..46], b"HTscHTLC");
+        assert!(matches!(
+            data_fork(&double),
+            Err(BookmarkError::InvalidContainer(Container::AppleDouble, _))
+        ));
+    }
+
+    #[test]
+    fn it_reads_binhex() {
+        let data = bookmark_bytes();
+        let text = format!("Saved from a newsgroup\n\n{}\n", to_binhex("Server", &data));
+
+        let (container, fork) = data_fork(text.as_bytes()).unwrap();
+        assert_eq!(container, Container::BinHex);
+        assert_eq!(fork, &data[..]);
+
+        // change one character of the encoded data
+        let mut corrupt = text.into_bytes();
+        let i = corrupt.iter().position(|c| *c == b':').unwrap() + 40;
+        corrupt[i] = if corrupt[i] == b'a' { b'b' } else { b'a' };
+        assert!(data_fork(&corrupt).is_err());
+    }
+
+    #[test]
+    fn it_reads_binhex_from_other_tools() {
+        // encoded by Python's binhex module, with a small resource fork after the bookmark
+        let file = include_bytes!("../fixtures/example.hqx");
+        let (container, fork) = data_fork(file).unwrap();
+
+        assert_eq!(container, Container::BinHex);
+        assert_eq!(fork, &include_bytes!("../fixtures/example")[..]);
+    }
+}
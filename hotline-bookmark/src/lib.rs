@@ -1,4 +1,7 @@
+pub mod address;
 pub mod bookmark;
+pub mod container;
+pub mod url;
 
 #[cfg(test)]
 mod tests {
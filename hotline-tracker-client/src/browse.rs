@@ -0,0 +1,523 @@
+use std::fmt::Write as _;
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+
+use tokio::sync::mpsc;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::screen::AlternateScreen;
+use termion::{clear, cursor, style};
+
+use hotline_bookmark::bookmark::{bookmark_path, Bookmark, BookmarkError};
+use hotline_tracker_client::{Error, ListingOptions};
+
+use crate::filter::ServerFilter;
+use crate::listing::{self, TrackerListing};
+use crate::output::ListedServer;
+
+/// how often the screen is redrawn even if nothing happened, so resizing the terminal is picked up
+const REDRAW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// rows used by everything other than the server list: title, column headings, separator,
+/// details pane and status line
+const CHROME_HEIGHT: usize = 9;
+
+/// lines of description shown in the details pane
+const DESCRIPTION_LINES: usize = 3;
+
+type FetchResult = (Vec<TrackerListing>, Vec<(String, Error)>);
+
+/// what the main loop should do after a key press
+#[derive(Debug, PartialEq, Eq)]
+pub enum Action {
+    None,
+    Refresh,
+    Bookmark,
+    Quit,
+}
+
+/// everything shown on screen, kept separate from the terminal so it's easy to reason about
+#[derive(Debug, Default)]
+pub struct Browser {
+    servers: Vec<ListedServer>,
+    search: String,
+    editing_search: bool,
+
+    /// index into the visible servers
+    selected: usize,
+
+    /// index of the first visible server shown in the list
+    scroll: usize,
+
+    tracker_count: usize,
+    refreshing: bool,
+    status: String,
+}
+
+impl Browser {
+    pub fn new(tracker_count: usize) -> Self {
+        Self {
+            tracker_count,
+            ..Default::default()
+        }
+    }
+
+    /// the servers that match the current search
+    pub fn visible(&self) -> Vec<&ListedServer> {
+        let filter = ServerFilter {
+            search: Some(self.search.clone()).filter(|s| !s.is_empty()),
+            ..Default::default()
+        };
+
+        self.servers.iter().filter(|s| filter.matches(s)).collect()
+    }
+
+    pub fn selected_server(&self) -> Option<&ListedServer> {
+        self.visible().get(self.selected).copied()
+    }
+
+    /// replace the servers after a refresh, keeping the same server selected if it's still listed
+    pub fn set_servers(&mut self, servers: Vec<ListedServer>) {
+        let selected = self.selected_server().map(ListedServer::address_with_port);
+        self.servers = servers;
+
+        if let Some(selected) = selected {
+            if let Some(i) = self
+                .visible()
+                .iter()
+                .position(|s| s.address_with_port() == selected)
+            {
+                self.selected = i;
+            }
+        }
+
+        self.clamp_selection();
+    }
+
+    pub fn set_status(&mut self, status: String) {
+        self.status = status;
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        self.selected = self.selected.saturating_add_signed(delta);
+        self.clamp_selection();
+    }
+
+    fn clamp_selection(&mut self) {
+        let count = self.visible().len();
+        self.selected = self.selected.min(count.saturating_sub(1));
+    }
+
+    /// scroll the list so the selected server is on screen
+    fn scroll_to_selection(&mut self, list_height: usize) {
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + list_height {
+            self.scroll = self.selected + 1 - list_height;
+        }
+    }
+
+    pub fn handle_key(&mut self, key: Key, list_height: usize) -> Action {
+        let page = list_height.max(1) as isize;
+
+        if self.editing_search {
+            let search = self.search.clone();
+
+            match key {
+                Key::Char('\n') => self.editing_search = false,
+                Key::Esc => {
+                    self.editing_search = false;
+                    self.search.clear();
+                }
+                Key::Backspace => {
+                    self.search.pop();
+                }
+                Key::Char(c) if !c.is_control() => self.search.push(c),
+                Key::Up => self.move_selection(-1),
+                Key::Down => self.move_selection(1),
+                Key::Ctrl('c') => return Action::Quit,
+                _ => {}
+            }
+
+            if self.search != search {
+                // the search changed, so start back at the top
+                self.selected = 0;
+                self.clamp_selection();
+            }
+
+            return Action::None;
+        }
+
+        match key {
+            Key::Char('q') | Key::Ctrl('c') => return Action::Quit,
+            Key::Char('r') => return Action::Refresh,
+            Key::Char('b') => return Action::Bookmark,
+            Key::Char('/') => self.editing_search = true,
+            Key::Esc => {
+                self.search.clear();
+                self.clamp_selection();
+            }
+            Key::Up | Key::Char('k') => self.move_selection(-1),
+            Key::Down | Key::Char('j') => self.move_selection(1),
+            Key::PageUp => self.move_selection(-page),
+            Key::PageDown => self.move_selection(page),
+            Key::Home | Key::Char('g') => self.selected = 0,
+            Key::End | Key::Char('G') => self.move_selection(isize::MAX),
+            _ => {}
+        }
+
+        Action::None
+    }
+
+    /// draw the whole screen into a string so it can be written in one go
+    pub fn render(&mut self, width: usize, height: usize) -> String {
+        let list_height = height.saturating_sub(CHROME_HEIGHT).max(1);
+        self.scroll_to_selection(list_height);
+
+        let mut lines: Vec<String> = vec![];
+        let visible = self.visible();
+
+        // title
+        let mut title = format!(
+            "{} servers from {} tracker(s)",
+            visible.len(),
+            self.tracker_count
+        );
+        if !self.search.is_empty() {
+            let _ = write!(title, " matching \"{}\"", self.search);
+        }
+        if self.refreshing {
+            title.push_str(" (refreshing...)");
+        }
+        lines.push(format!(
+            "{}{}{}",
+            style::Bold,
+            fit(&title, width),
+            style::Reset
+        ));
+
+        // the server list
+        let name_width = width.saturating_sub(30);
+        lines.push(format!(
+            "{}{} {:<21} {:>6}{}",
+            style::Underline,
+            fit("Name", name_width),
+            "Address",
+            "Users",
+            style::Reset
+        ));
+
+        for (i, server) in visible
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(list_height)
+        {
+            let row = format!(
+                "{} {:<21} {:>6}",
+                fit(&server.name, name_width),
+                server.address_with_port(),
+                server.users_online
+            );
+
+            if i == self.selected {
+                lines.push(format!("{}{}{}", style::Invert, row, style::Reset));
+            } else {
+                lines.push(row);
+            }
+        }
+
+        while lines.len() < list_height + 2 {
+            lines.push(String::new());
+        }
+
+        // details of the selected server
+        lines.push("─".repeat(width));
+        match visible.get(self.selected) {
+            Some(server) => {
+                lines.push(format!(
+                    "{}{}{}",
+                    style::Bold,
+                    fit(&server.name, width),
+                    style::Reset
+                ));
+                lines.push(fit(
+                    &format!(
+                        "{} - {} users - listed on {}",
+                        server.address_with_port(),
+                        server.users_online,
+                        server.trackers.join(", ")
+                    ),
+                    width,
+                ));
+
+                let description = server.description.replace(['\r', '\n'], " ");
+                let mut description = wrap(&description, width);
+                description.resize(DESCRIPTION_LINES, String::new());
+                lines.extend(description);
+            }
+            None => {
+                lines.resize(lines.len() + DESCRIPTION_LINES + 2, String::new());
+            }
+        }
+
+        // status line
+        let status = if self.editing_search {
+            format!("Search: {}", self.search)
+        } else if !self.status.is_empty() {
+            self.status.clone()
+        } else {
+            "/ search  r refresh  b bookmark  q quit".into()
+        };
+        lines.push(fit(&status, width));
+
+        let mut screen = format!("{}{}", clear::All, cursor::Goto(1, 1));
+        screen.push_str(&lines.join("\r\n"));
+        screen
+    }
+}
+
+/// pad or truncate a string to exactly `width` characters
+fn fit(s: &str, width: usize) -> String {
+    let truncated: String = s.chars().take(width).collect();
+    format!("{truncated:<width$}")
+}
+
+/// break text into lines of at most `width` characters, on spaces where possible
+fn wrap(s: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+
+    for word in s.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+
+        // words that are too long for a line get split
+        while word.len() > width && width > 0 {
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            lines.push(word.drain(..width).collect());
+        }
+
+        let word: String = word.into_iter().collect();
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&word);
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+fn write_bookmark(dir: &Path, server: &ListedServer) -> Result<PathBuf, BookmarkError> {
+    let path = bookmark_path(dir, &server.name, "hlbm");
+    Bookmark::new(server.address_with_port()).write_to_file(&path.to_string_lossy())?;
+
+    Ok(path)
+}
+
+fn fetch(
+    trackers: &[String],
+    options: &ListingOptions,
+) -> Pin<Box<dyn Future<Output = FetchResult>>> {
+    let trackers = trackers.to_vec();
+    let options = options.clone();
+
+    Box::pin(async move { listing::fetch_all(&trackers, &options).await })
+}
+
+/// run the browser until the user quits
+pub async fn run(
+    trackers: Vec<String>,
+    options: ListingOptions,
+    refresh_interval: Duration,
+    bookmark_dir: PathBuf,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // reading keys blocks, so it happens on its own thread
+    let (tx, mut keys) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for key in std::io::stdin().keys().flatten() {
+            if tx.send(key).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut screen = AlternateScreen::from(std::io::stdout().into_raw_mode()?);
+    write!(screen, "{}", cursor::Hide)?;
+
+    let mut browser = Browser::new(trackers.len());
+    let mut refresh = interval(refresh_interval);
+    refresh.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut redraw = interval(REDRAW_INTERVAL);
+    let mut pending: Option<Pin<Box<dyn Future<Output = FetchResult>>>> = None;
+
+    let result = loop {
+        let (width, height) = termion::terminal_size()?;
+        let (width, height) = (width as usize, height as usize);
+        let list_height = height.saturating_sub(CHROME_HEIGHT).max(1);
+
+        browser.refreshing = pending.is_some();
+        write!(screen, "{}", browser.render(width, height))?;
+        screen.flush()?;
+
+        tokio::select! {
+            _ = refresh.tick(), if pending.is_none() => {
+                pending = Some(fetch(&trackers, &options));
+            }
+            (listings, failures) = async { pending.as_mut().unwrap().await }, if pending.is_some() => {
+                pending = None;
+                browser.set_servers(listing::merge(&listings));
+                browser.set_status(match failures.first() {
+                    Some((tracker, err)) if failures.len() > 1 => {
+                        format!("{tracker}: {err} (and {} more)", failures.len() - 1)
+                    }
+                    Some((tracker, err)) => format!("{tracker}: {err}"),
+                    None => String::new(),
+                });
+            }
+            key = keys.recv() => {
+                let key = match key {
+                    Some(key) => key,
+                    None => break Ok(()),
+                };
+
+                browser.set_status(String::new());
+                match browser.handle_key(key, list_height) {
+                    Action::Quit => break Ok(()),
+                    Action::Refresh => refresh.reset_immediately(),
+                    Action::Bookmark => {
+                        if let Some(server) = browser.selected_server() {
+                            let status = match write_bookmark(&bookmark_dir, server) {
+                                Ok(path) => format!("Wrote bookmark: {}", path.display()),
+                                Err(err) => format!("Failed to write bookmark: {err}"),
+                            };
+                            browser.set_status(status);
+                        }
+                    }
+                    Action::None => {}
+                }
+            }
+            _ = redraw.tick() => {}
+        }
+    };
+
+    write!(screen, "{}", cursor::Show)?;
+    screen.flush()?;
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hotline_tracker::ServerRecord;
+
+    fn browser() -> Browser {
+        let servers = ["Alpha", "Beta", "Gamma", "Delta"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let record = ServerRecord {
+                    address: [10, 0, 0, i as u8 + 1].into(),
+                    name: (*name).into(),
+                    ..Default::default()
+                };
+                ListedServer::new("tracker", &record)
+            })
+            .collect();
+
+        let mut browser = Browser::new(1);
+        browser.set_servers(servers);
+        browser
+    }
+
+    fn selected_name(browser: &Browser) -> &str {
+        browser.selected_server().map(|s| s.name.as_str()).unwrap()
+    }
+
+    #[test]
+    fn it_moves_the_selection() {
+        let mut browser = browser();
+
+        assert_eq!(browser.handle_key(Key::Down, 2), Action::None);
+        assert_eq!(selected_name(&browser), "Beta");
+
+        browser.handle_key(Key::PageDown, 10);
+        assert_eq!(selected_name(&browser), "Delta");
+
+        browser.handle_key(Key::Char('g'), 10);
+        assert_eq!(selected_name(&browser), "Alpha");
+
+        browser.handle_key(Key::Up, 10);
+        assert_eq!(selected_name(&browser), "Alpha");
+
+        assert_eq!(browser.handle_key(Key::Char('b'), 10), Action::Bookmark);
+        assert_eq!(browser.handle_key(Key::Char('q'), 10), Action::Quit);
+    }
+
+    #[test]
+    fn it_searches() {
+        let mut browser = browser();
+
+        browser.handle_key(Key::Char('/'), 10);
+        for c in "ta".chars() {
+            browser.handle_key(Key::Char(c), 10);
+        }
+        browser.handle_key(Key::Char('\n'), 10);
+
+        let names: Vec<&str> = browser.visible().iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["Beta", "Delta"]);
+
+        // keys go back to navigating once the search is done
+        assert_eq!(browser.handle_key(Key::Char('q'), 10), Action::Quit);
+
+        browser.handle_key(Key::Esc, 10);
+        assert_eq!(browser.visible().len(), 4);
+    }
+
+    #[test]
+    fn it_keeps_the_selection_after_a_refresh() {
+        let mut browser = browser();
+        browser.handle_key(Key::Down, 10);
+        browser.handle_key(Key::Down, 10);
+        assert_eq!(selected_name(&browser), "Gamma");
+
+        // Alpha went away
+        let servers = browser.servers[1..].to_vec();
+        browser.set_servers(servers);
+        assert_eq!(selected_name(&browser), "Gamma");
+    }
+
+    #[test]
+    fn it_scrolls_to_the_selection() {
+        let mut browser = browser();
+        browser.handle_key(Key::End, 2);
+
+        let screen = browser.render(60, CHROME_HEIGHT + 2);
+        assert!(screen.contains("Delta"));
+        assert!(!screen.contains("Alpha"));
+    }
+
+    #[test]
+    fn it_wraps_text() {
+        assert_eq!(
+            wrap("the quick brown fox", 10),
+            vec!["the quick", "brown fox"]
+        );
+        assert_eq!(wrap("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+    }
+}
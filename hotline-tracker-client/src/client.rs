@@ -7,6 +7,10 @@ use tokio::io::AsyncWriteExt;
 
 use hotline_tracker::header;
 use hotline_tracker::{Header, ServerRecord, TrackerPacket, UpdateRecord};
+use macroman_tools::{string_to_macroman, MacRomanString};
+
+use crate::tracker_address::{TrackerAddress, DEFAULT_TRACKER_PORT};
+use crate::Error;
 
 // establish connection
 // send HELO packet
@@ -19,15 +23,36 @@ pub struct Client {
 }
 
 impl Client {
-    pub async fn connect(address: &str, port: u16) -> Result<Self, Box<dyn std::error::Error>> {
-        // eprintln!("connecting... to {address}:{port}");
-
-        let address = format!("{address}:{port}");
-        let mut stream = TcpStream::connect(address).await?;
+    /// connect to a tracker. `password` is only needed for private trackers; if it's given, it's
+    /// sent along with the header.
+    pub async fn connect(address: &TrackerAddress, password: Option<&str>) -> Result<Self, Error> {
+        // a tracker's hostname can have several addresses. try each of them until one works.
+        let addrs = address.resolve(DEFAULT_TRACKER_PORT).await?;
+        let mut result = Err(std::io::ErrorKind::NotFound.into());
+        for addr in addrs {
+            result = TcpStream::connect(addr).await;
+            if result.is_ok() {
+                break;
+            }
+        }
+        let mut stream = result.map_err(Error::Connect)?;
 
         let mut buf = BytesMut::with_capacity(6);
-        Header::default().put_slice(&mut buf);
-        stream.write_all(&buf).await?;
+        match password {
+            Some(password) => {
+                let password = string_to_macroman(password);
+                if password.len() > 255 {
+                    return Err(Error::PasswordTooLong);
+                }
+
+                Header::with_password().put_slice(&mut buf);
+                MacRomanString::<255>::from(&password[..]).write_to_buf(&mut buf);
+            }
+            None => {
+                Header::default().put_slice(&mut buf);
+            }
+        }
+        stream.write_all(&buf).await.map_err(Error::Connect)?;
 
         let codec = HLTrackerCodec::new();
         let framed_stream = Framed::new(stream, codec);
@@ -60,9 +85,15 @@ impl HLTrackerCodec {
     }
 }
 
+impl Default for HLTrackerCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Decoder for HLTrackerCodec {
     type Item = TrackerPacket;
-    type Error = std::io::Error;
+    type Error = Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
         if self.state == State::Initialized {
@@ -72,14 +103,15 @@ impl Decoder for HLTrackerCodec {
                     self.state = State::ReceivedHeader;
                     return Ok(Some(TrackerPacket::Header));
                 } else {
-                    panic!(
-                        "invalid header {:?} / {}",
-                        header.magic_word, header.version
-                    );
+                    return Err(Error::InvalidHeader {
+                        magic_word: header.magic_word,
+                        version: header.version,
+                    });
                 }
             }
 
-            panic!("failed to get header.");
+            // not enough data for a header yet
+            return Ok(None);
         }
 
         if let Some(expected_total) = self.expected_total_servers {
@@ -120,4 +152,23 @@ impl Decoder for HLTrackerCodec {
             Ok(server_record)
         }
     }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if let Some(packet) = self.decode(src)? {
+            return Ok(Some(packet));
+        }
+
+        // the connection closed. that's only expected before the tracker has sent anything (it
+        // turned us away) or once every server it promised has arrived.
+        let complete = matches!(
+            self.expected_total_servers,
+            Some(expected_total) if expected_total == self.received_server_count
+        );
+
+        if !src.is_empty() || (self.state == State::ReceivedHeader && !complete) {
+            return Err(Error::Truncated);
+        }
+
+        Ok(None)
+    }
 }
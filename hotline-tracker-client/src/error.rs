@@ -0,0 +1,97 @@
+use thiserror::Error;
+
+use crate::tracker_address::InvalidTrackerAddress;
+
+/// everything that can go wrong talking to a tracker
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    InvalidAddress(#[from] InvalidTrackerAddress),
+
+    #[error("Couldn't look up {host}: {source}")]
+    Resolve {
+        host: String,
+        source: std::io::Error,
+    },
+
+    #[error("Couldn't connect: {}", describe_io_error(.0))]
+    Connect(std::io::Error),
+
+    #[error("Timed out connecting")]
+    ConnectTimeout,
+
+    #[error("Timed out waiting for the tracker")]
+    ReadTimeout,
+
+    #[error(
+        "Not a Hotline tracker (it sent {:?} version {version} instead of \"HTRK\" version 1)",
+        String::from_utf8_lossy(magic_word)
+    )]
+    InvalidHeader { magic_word: [u8; 4], version: u16 },
+
+    #[error("Not a Hotline tracker (it didn't start with a header)")]
+    MissingHeader,
+
+    #[error("The tracker closed the connection without sending a listing")]
+    NoListing,
+
+    #[error("The listing was cut off")]
+    Truncated,
+
+    #[error("Tracker password is too long (max 255 bytes)")]
+    PasswordTooLong,
+
+    #[error("IO Error: {}", describe_io_error(.0))]
+    Io(#[from] std::io::Error),
+}
+
+impl Error {
+    /// whether trying again might work. Timeouts, refused connections and dropped connections can
+    /// be temporary; a bad address or something that isn't a tracker won't fix itself.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Error::Connect(_)
+                | Error::ConnectTimeout
+                | Error::ReadTimeout
+                | Error::Truncated
+                | Error::Io(_)
+        )
+    }
+}
+
+/// io errors without the "(os error 111)" on the end
+fn describe_io_error(err: &std::io::Error) -> String {
+    use std::io::ErrorKind;
+
+    match err.kind() {
+        ErrorKind::ConnectionRefused => "connection refused".into(),
+        ErrorKind::ConnectionReset => "connection reset".into(),
+        ErrorKind::TimedOut => "timed out".into(),
+        ErrorKind::HostUnreachable => "host unreachable".into(),
+        ErrorKind::NetworkUnreachable => "network unreachable".into(),
+        _ => err.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_describes_errors() {
+        let err = Error::InvalidHeader {
+            magic_word: *b"SSH-",
+            version: 0x322e,
+        };
+        assert_eq!(
+            err.to_string(),
+            "Not a Hotline tracker (it sent \"SSH-\" version 12846 instead of \"HTRK\" version 1)"
+        );
+
+        let err = Error::Connect(std::io::ErrorKind::ConnectionRefused.into());
+        assert_eq!(err.to_string(), "Couldn't connect: connection refused");
+        assert!(err.is_transient());
+        assert!(!Error::NoListing.is_transient());
+    }
+}
@@ -0,0 +1,347 @@
+use futures::stream::{self, BoxStream};
+use futures::StreamExt;
+
+use tokio::time::{timeout, Duration};
+
+use hotline_tracker::{ServerRecord, TrackerPacket, UpdateRecord};
+
+use crate::client::Client;
+use crate::tracker_address::TrackerAddress;
+use crate::Error;
+
+/// how long to wait for a tracker to accept a connection, and then for each part of the listing
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// how long to wait before retrying a tracker the first time
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// settings for fetching a listing. The defaults work for public trackers.
+#[derive(Debug, Clone)]
+pub struct ListingOptions {
+    /// only needed for private trackers
+    pub password: Option<String>,
+
+    /// how long to wait for the connection, including looking up the tracker's address
+    pub connect_timeout: Duration,
+
+    /// how long to wait for each part of the listing to arrive
+    pub read_timeout: Duration,
+
+    /// how many more times to try after a failure that might not happen again, like a timeout or a
+    /// refused connection. Only used by `fetch_listing_with`.
+    pub retries: u32,
+
+    /// how long to wait before the first retry. The wait doubles after each one.
+    pub retry_delay: Duration,
+}
+
+impl Default for ListingOptions {
+    fn default() -> Self {
+        Self {
+            password: None,
+            connect_timeout: DEFAULT_TIMEOUT,
+            read_timeout: DEFAULT_TIMEOUT,
+            retries: 0,
+            retry_delay: DEFAULT_RETRY_DELAY,
+        }
+    }
+}
+
+/// a tracker's complete listing
+#[derive(Debug, Clone)]
+pub struct Listing {
+    /// the tracker's summary of the listing, including how many servers it has
+    pub update: Option<UpdateRecord>,
+    pub servers: Vec<ServerRecord>,
+}
+
+/// one part of a listing, as it arrives
+#[derive(Debug, Clone)]
+pub enum ListingEvent {
+    Update(UpdateRecord),
+    Server(Box<ServerRecord>),
+}
+
+/// a listing being received from a tracker
+pub type ListingStream = BoxStream<'static, Result<ListingEvent, Error>>;
+
+/// fetch the complete listing from a tracker
+pub async fn fetch_listing(addr: &TrackerAddress) -> Result<Listing, Error> {
+    fetch_listing_with(addr, &ListingOptions::default()).await
+}
+
+/// fetch the complete listing from a tracker with the given options, retrying if it fails in a way
+/// that might not happen next time
+pub async fn fetch_listing_with(
+    addr: &TrackerAddress,
+    options: &ListingOptions,
+) -> Result<Listing, Error> {
+    let mut delay = options.retry_delay;
+    let mut retries = options.retries;
+
+    loop {
+        match fetch_listing_once(addr, options).await {
+            Err(err) if retries > 0 && err.is_transient() => {
+                retries -= 1;
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+async fn fetch_listing_once(
+    addr: &TrackerAddress,
+    options: &ListingOptions,
+) -> Result<Listing, Error> {
+    let mut listing = Listing {
+        update: None,
+        servers: vec![],
+    };
+
+    let mut events = stream_listing(addr, options).await?;
+    while let Some(event) = events.next().await {
+        match event? {
+            ListingEvent::Update(update) => listing.update = Some(update),
+            ListingEvent::Server(server) => listing.servers.push(*server),
+        }
+    }
+
+    Ok(listing)
+}
+
+/// connect to a tracker and receive its listing as it arrives. This returns once the tracker has
+/// accepted the connection and sent its header, so a tracker that turns us away is an error here
+/// rather than an empty stream.
+pub async fn stream_listing(
+    addr: &TrackerAddress,
+    options: &ListingOptions,
+) -> Result<ListingStream, Error> {
+    let mut client = timeout(
+        options.connect_timeout,
+        Client::connect(addr, options.password.as_deref()),
+    )
+    .await
+    .map_err(|_| Error::ConnectTimeout)??;
+
+    let read_timeout = options.read_timeout;
+
+    match timeout(read_timeout, client.framed_stream.next()).await {
+        Err(_) => return Err(Error::ReadTimeout),
+        Ok(None) => return Err(Error::NoListing),
+        Ok(Some(Err(err))) => return Err(err),
+        Ok(Some(Ok(TrackerPacket::Header))) => {}
+        Ok(Some(Ok(_))) => return Err(Error::MissingHeader),
+    }
+
+    let events = stream::unfold(Some(client), move |client| async move {
+        let mut client = client?;
+
+        loop {
+            let packet = match timeout(read_timeout, client.framed_stream.next()).await {
+                Err(_) => return Some((Err(Error::ReadTimeout), None)),
+                Ok(None) => return None,
+                Ok(Some(Err(err))) => return Some((Err(err), None)),
+                Ok(Some(Ok(packet))) => packet,
+            };
+
+            let event = match packet {
+                TrackerPacket::Update(update) => ListingEvent::Update(update),
+                TrackerPacket::Server(server) => ListingEvent::Server(server),
+                TrackerPacket::Complete => return None,
+                TrackerPacket::Header => continue,
+            };
+
+            return Some((Ok(event), Some(client)));
+        }
+    });
+
+    Ok(events.boxed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::SocketAddr;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use bytes::BytesMut;
+    use hotline_tracker::Header;
+
+    /// a tracker that sends `response` to anyone who connects
+    async fn fake_tracker(response: Vec<u8>) -> TrackerAddress {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let response = response.clone();
+
+                tokio::spawn(async move {
+                    let mut header = [0u8; 6];
+                    let _ = socket.read_exact(&mut header).await;
+                    let _ = socket.write_all(&response).await;
+
+                    // keep the connection open for a moment so timeouts can be tested
+                    tokio::time::sleep(Duration::from_millis(500)).await;
+                });
+            }
+        });
+
+        addr.to_string().parse().unwrap()
+    }
+
+    fn listing_bytes(servers: &[ServerRecord]) -> BytesMut {
+        let mut buf = BytesMut::new();
+        Header::default().put_slice(&mut buf);
+
+        let data_size: usize = servers.iter().map(ServerRecord::data_size).sum();
+        UpdateRecord {
+            version: 1,
+            remaining_data_size: data_size as u16 + 4,
+            total_servers: servers.len() as u16,
+            remaining_servers: servers.len() as u16,
+        }
+        .put_slice(&mut buf);
+
+        for server in servers {
+            server.put_slice(&mut buf);
+        }
+
+        buf
+    }
+
+    fn options() -> ListingOptions {
+        ListingOptions {
+            read_timeout: Duration::from_millis(100),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn it_fetches_a_listing() {
+        let servers = vec![
+            ServerRecord {
+                name: "One".into(),
+                ..Default::default()
+            },
+            ServerRecord {
+                name: "Two".into(),
+                port: 5600,
+                ..Default::default()
+            },
+        ];
+        let addr = fake_tracker(listing_bytes(&servers).to_vec()).await;
+
+        let listing = fetch_listing_with(&addr, &options()).await.unwrap();
+        assert_eq!(listing.update.unwrap().total_servers, 2);
+        assert_eq!(listing.servers.len(), 2);
+        assert_eq!(listing.servers[1].name.as_string(), "Two");
+    }
+
+    #[tokio::test]
+    async fn it_reports_bad_trackers() {
+        let servers = vec![ServerRecord::default()];
+        let listing = listing_bytes(&servers);
+
+        // not a tracker at all
+        let addr = fake_tracker(b"SSH-2.0-OpenSSH\r\n".to_vec()).await;
+        assert!(matches!(
+            fetch_listing_with(&addr, &options()).await,
+            Err(Error::InvalidHeader { .. })
+        ));
+
+        // the tracker stops partway through a server record, then goes quiet
+        let addr = fake_tracker(listing[..listing.len() - 3].to_vec()).await;
+        assert!(matches!(
+            fetch_listing_with(&addr, &options()).await,
+            Err(Error::ReadTimeout)
+        ));
+
+        // the tracker hangs up without sending anything
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr: TrackerAddress = listener.local_addr().unwrap().to_string().parse().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut header = [0u8; 6];
+            let _ = socket.read_exact(&mut header).await;
+        });
+        assert!(matches!(
+            fetch_listing_with(&addr, &options()).await,
+            Err(Error::NoListing)
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_retries_failed_fetches() {
+        let servers = vec![ServerRecord::default()];
+        let listing = listing_bytes(&servers);
+
+        // the first connection gets cut off, the next one gets the whole listing
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr: TrackerAddress = listener.local_addr().unwrap().to_string().parse().unwrap();
+        tokio::spawn(async move {
+            for response in [&listing[..listing.len() - 3], &listing[..]] {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut header = [0u8; 6];
+                let _ = socket.read_exact(&mut header).await;
+                let _ = socket.write_all(response).await;
+            }
+        });
+
+        let retrying = ListingOptions {
+            retries: 2,
+            retry_delay: Duration::from_millis(10),
+            ..options()
+        };
+        let listing = fetch_listing_with(&addr, &retrying).await.unwrap();
+        assert_eq!(listing.servers.len(), 1);
+
+        // a tracker that isn't one won't become one by trying again
+        let addr = fake_tracker(b"SSH-2.0-OpenSSH\r\n".to_vec()).await;
+        let started = std::time::Instant::now();
+        let slow_retries = ListingOptions {
+            retries: 2,
+            retry_delay: Duration::from_secs(10),
+            ..options()
+        };
+        assert!(matches!(
+            fetch_listing_with(&addr, &slow_retries).await,
+            Err(Error::InvalidHeader { .. })
+        ));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn it_reports_truncated_listings() {
+        let servers = vec![ServerRecord::default(), ServerRecord::default()];
+        let listing = listing_bytes(&servers);
+        let truncated = listing[..listing.len() - 3].to_vec();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr: TrackerAddress = listener.local_addr().unwrap().to_string().parse().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut header = [0u8; 6];
+            let _ = socket.read_exact(&mut header).await;
+            let _ = socket.write_all(&truncated).await;
+        });
+
+        let mut events = stream_listing(&addr, &options()).await.unwrap();
+        assert!(matches!(
+            events.next().await,
+            Some(Ok(ListingEvent::Update(_)))
+        ));
+        assert!(matches!(
+            events.next().await,
+            Some(Ok(ListingEvent::Server(_)))
+        ));
+        assert!(matches!(events.next().await, Some(Err(Error::Truncated))));
+        assert!(events.next().await.is_none());
+    }
+}
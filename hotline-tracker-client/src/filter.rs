@@ -0,0 +1,179 @@
+use std::cmp::Ordering;
+
+use regex::Regex;
+
+use crate::output::ListedServer;
+
+/// narrows down a listing to the servers someone is interested in. Every condition that's set has
+/// to match.
+#[derive(Debug, Default)]
+pub struct ServerFilter {
+    /// case-insensitive text to look for in the name or description
+    pub search: Option<String>,
+
+    /// a pattern to match against the name or description
+    pub regex: Option<Regex>,
+
+    pub min_users: Option<u16>,
+    pub max_users: Option<u16>,
+
+    /// the start of the server's address, like `192.168.` or `10.0.0.1`
+    pub address_prefix: Option<String>,
+}
+
+impl ServerFilter {
+    pub fn matches(&self, server: &ListedServer) -> bool {
+        if let Some(search) = &self.search {
+            let search = search.to_lowercase();
+            if !server.name.to_lowercase().contains(&search)
+                && !server.description.to_lowercase().contains(&search)
+            {
+                return false;
+            }
+        }
+
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(&server.name) && !regex.is_match(&server.description) {
+                return false;
+            }
+        }
+
+        if matches!(self.min_users, Some(min) if server.users_online < min) {
+            return false;
+        }
+
+        if matches!(self.max_users, Some(max) if server.users_online > max) {
+            return false;
+        }
+
+        if let Some(prefix) = &self.address_prefix {
+            if !server.address_with_port().starts_with(prefix.as_str()) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// what to order the listing by
+#[derive(clap::ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    /// alphabetically by name, ignoring case
+    Name,
+
+    /// busiest servers first
+    Users,
+
+    /// by address, then port
+    Address,
+}
+
+impl SortKey {
+    fn compare(&self, a: &ListedServer, b: &ListedServer) -> Ordering {
+        match self {
+            SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SortKey::Users => b.users_online.cmp(&a.users_online),
+            SortKey::Address => (a.address, a.port).cmp(&(b.address, b.port)),
+        }
+    }
+}
+
+/// sort servers in place. The sort is stable, so servers that compare equal stay in the order
+/// the trackers listed them.
+pub fn sort_servers(servers: &mut [ListedServer], key: SortKey, reverse: bool) {
+    servers.sort_by(|a, b| {
+        let ordering = key.compare(a, b);
+        if reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hotline_tracker::ServerRecord;
+
+    fn server(address: &str, name: &str, description: &str, users_online: u16) -> ListedServer {
+        let record = ServerRecord {
+            address: address.parse().unwrap(),
+            name: name.into(),
+            description: description.into(),
+            users_online,
+            ..Default::default()
+        };
+
+        ListedServer::new("tracker", &record)
+    }
+
+    fn names(servers: &[ListedServer]) -> Vec<&str> {
+        servers.iter().map(|s| s.name.as_str()).collect()
+    }
+
+    #[test]
+    fn it_filters_servers() {
+        let servers = [
+            server("10.0.0.1", "Retro Games", "classic mac games", 12),
+            server("10.0.0.2", "Chat", "just chatting", 0),
+            server("192.168.1.9", "Mac Stuff", "files", 4),
+        ];
+
+        let filtered = |filter: ServerFilter| -> Vec<&str> {
+            servers
+                .iter()
+                .filter(|s| filter.matches(s))
+                .map(|s| s.name.as_str())
+                .collect()
+        };
+
+        let search = ServerFilter {
+            search: Some("MAC".into()),
+            ..Default::default()
+        };
+        assert_eq!(filtered(search), vec!["Retro Games", "Mac Stuff"]);
+
+        let regex = ServerFilter {
+            regex: Some(Regex::new("^(Chat|Mac)").unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(filtered(regex), vec!["Chat", "Mac Stuff"]);
+
+        let users = ServerFilter {
+            min_users: Some(1),
+            max_users: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(filtered(users), vec!["Mac Stuff"]);
+
+        let address = ServerFilter {
+            address_prefix: Some("10.0.".into()),
+            ..Default::default()
+        };
+        assert_eq!(filtered(address), vec!["Retro Games", "Chat"]);
+    }
+
+    #[test]
+    fn it_sorts_servers() {
+        let mut servers = vec![
+            server("10.0.0.10", "beta", "", 3),
+            server("10.0.0.9", "Alpha", "", 1),
+            server("10.0.0.100", "gamma", "", 7),
+        ];
+
+        sort_servers(&mut servers, SortKey::Name, false);
+        assert_eq!(names(&servers), vec!["Alpha", "beta", "gamma"]);
+
+        sort_servers(&mut servers, SortKey::Users, false);
+        assert_eq!(names(&servers), vec!["gamma", "beta", "Alpha"]);
+
+        sort_servers(&mut servers, SortKey::Address, false);
+        assert_eq!(names(&servers), vec!["Alpha", "beta", "gamma"]);
+
+        sort_servers(&mut servers, SortKey::Address, true);
+        assert_eq!(names(&servers), vec!["gamma", "beta", "Alpha"]);
+    }
+}
@@ -0,0 +1,29 @@
+//! A client for Hotline trackers: fetch the list of servers from a tracker, or register a server
+//! with one.
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), hotline_tracker_client::Error> {
+//! let tracker = "hltracker.com".parse()?;
+//! let listing = hotline_tracker_client::fetch_listing(&tracker).await?;
+//!
+//! for server in listing.servers {
+//!     println!("{} [{}]", server.name, server.address_with_port());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod client;
+mod error;
+mod fetch;
+mod register;
+pub mod tracker_address;
+
+pub use client::Client;
+pub use error::Error;
+pub use fetch::{
+    fetch_listing, fetch_listing_with, stream_listing, Listing, ListingEvent, ListingOptions,
+    ListingStream, DEFAULT_RETRY_DELAY, DEFAULT_TIMEOUT,
+};
+pub use register::{register, register_with, RegisterOptions};
+pub use tracker_address::TrackerAddress;
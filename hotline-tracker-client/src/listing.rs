@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+
+use hotline_tracker::ServerRecord;
+use hotline_tracker_client::{fetch_listing_with, Error, ListingOptions, TrackerAddress};
+
+use crate::output::ListedServer;
+
+/// everything a single tracker sent us
+#[derive(Debug)]
+pub struct TrackerListing {
+    pub tracker: String,
+
+    /// how many servers the tracker said it has, which can differ from how many it sent
+    pub total_servers: Option<u16>,
+    pub servers: Vec<ServerRecord>,
+}
+
+/// connect to a tracker and read its full listing
+pub async fn fetch(tracker: &str, options: &ListingOptions) -> Result<TrackerListing, Error> {
+    let address: TrackerAddress = tracker.parse()?;
+    let listing = fetch_listing_with(&address, options).await?;
+
+    Ok(TrackerListing {
+        tracker: tracker.into(),
+        total_servers: listing.update.map(|update| update.total_servers),
+        servers: listing.servers,
+    })
+}
+
+/// fetch listings from every tracker at once. Trackers that fail are returned separately, along
+/// with their error.
+pub async fn fetch_all(
+    trackers: &[String],
+    options: &ListingOptions,
+) -> (Vec<TrackerListing>, Vec<(String, Error)>) {
+    let results =
+        futures::future::join_all(trackers.iter().map(|tracker| fetch(tracker, options))).await;
+
+    let mut listings = vec![];
+    let mut failures = vec![];
+    for (tracker, result) in trackers.iter().zip(results) {
+        match result {
+            Ok(listing) => listings.push(listing),
+            Err(err) => failures.push((tracker.clone(), err)),
+        }
+    }
+
+    (listings, failures)
+}
+
+/// combine listings from several trackers. Servers listed on more than one tracker (same address
+/// and port) show up once, with every tracker that listed them. Servers are kept in the order they
+/// were first seen.
+pub fn merge(listings: &[TrackerListing]) -> Vec<ListedServer> {
+    let mut merged: Vec<ListedServer> = vec![];
+    let mut seen: HashMap<String, usize> = HashMap::new();
+
+    for listing in listings {
+        for server in &listing.servers {
+            let key = server.address_with_port();
+
+            match seen.get(&key) {
+                Some(&i) => {
+                    let trackers = &mut merged[i].trackers;
+                    if !trackers.contains(&listing.tracker) {
+                        trackers.push(listing.tracker.clone());
+                    }
+                }
+                None => {
+                    seen.insert(key, merged.len());
+                    merged.push(ListedServer::new(&listing.tracker, server));
+                }
+            }
+        }
+    }
+
+    merged
+}
+
+/// the number of servers the trackers said they have, added up. Servers listed on more than one
+/// tracker are counted for each.
+pub fn total_servers(listings: &[TrackerListing]) -> Option<u32> {
+    listings
+        .iter()
+        .filter_map(|listing| listing.total_servers)
+        .map(u32::from)
+        .reduce(|a, b| a + b)
+}
+
+/// parse a list of trackers, one per line. Blank lines and lines starting with `#` are skipped.
+pub fn parse_tracker_list(s: &str) -> Vec<String> {
+    s.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(address: &str, port: u16, name: &str) -> ServerRecord {
+        ServerRecord {
+            address: address.parse().unwrap(),
+            port,
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn it_merges_listings() {
+        let listings = vec![
+            TrackerListing {
+                tracker: "a.example.com".into(),
+                total_servers: Some(2),
+                servers: vec![
+                    server("10.0.0.1", 5500, "one"),
+                    server("10.0.0.2", 5500, "two"),
+                ],
+            },
+            TrackerListing {
+                tracker: "b.example.com".into(),
+                total_servers: Some(3),
+                servers: vec![
+                    server("10.0.0.2", 5500, "two again"),
+                    server("10.0.0.2", 5600, "three"),
+                ],
+            },
+        ];
+
+        let merged = merge(&listings);
+        assert_eq!(merged.len(), 3);
+
+        assert_eq!(merged[0].trackers, vec!["a.example.com"]);
+        assert_eq!(merged[1].name, "two");
+        assert_eq!(merged[1].trackers, vec!["a.example.com", "b.example.com"]);
+        assert_eq!(merged[2].trackers, vec!["b.example.com"]);
+
+        assert_eq!(total_servers(&listings), Some(5));
+        assert_eq!(total_servers(&[]), None);
+    }
+
+    #[test]
+    fn it_parses_tracker_lists() {
+        let trackers = parse_tracker_list("# trackers\nhltracker.com\n\n  tracker.example.com  \n");
+        assert_eq!(trackers, vec!["hltracker.com", "tracker.example.com"]);
+    }
+}
@@ -1,59 +1,276 @@
-use tokio::net::UdpSocket;
+use std::path::PathBuf;
 
-use futures::StreamExt;
+use tokio::time::Duration;
 
-mod client;
+mod browse;
+mod filter;
+mod listing;
+mod output;
+mod registration;
+mod server_id;
+mod watch;
 
-use client::Client;
+use filter::{ServerFilter, SortKey};
+use output::OutputFormat;
+use registration::{RegistrationConfig, RegistrationConfigError};
 
-use hotline_tracker::{RegistrationRecord, TrackerPacket, UpdateRecord};
-use macroman_tools::MacRomanString;
+use hotline_tracker::RegistrationRecord;
+use hotline_tracker_client::{register_with, Error, ListingOptions, RegisterOptions};
 
 use clap::Parser;
 
-use termion::style;
+use env_logger::{Builder, Env};
+
+/// the trackers to list servers from, shared by `list` and `browse`
+#[derive(Parser, Debug)]
+struct TrackerArgs {
+    /// The trackers to list servers from, as `host`, `host:port` or `[ipv6]:port`
+    trackers: Vec<String>,
+
+    /// A file listing more trackers to query, one per line
+    #[clap(short = 'i', long)]
+    tracker_file: Option<String>,
+
+    /// Password for listing servers on a private tracker
+    #[clap(long)]
+    password: Option<String>,
+
+    /// Seconds to wait for a tracker to accept the connection
+    #[clap(long, default_value = "10")]
+    connect_timeout: u64,
+
+    /// Seconds to wait for each part of the listing before giving up on a tracker
+    #[clap(long, default_value = "10")]
+    timeout: u64,
+
+    /// How many more times to try a tracker that timed out, refused the connection or cut the
+    /// listing short. The wait between tries starts at a second and doubles each time.
+    #[clap(long, default_value = "2")]
+    retries: u32,
+}
+
+impl TrackerArgs {
+    fn trackers(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut trackers = self.trackers.clone();
+        if let Some(tracker_file) = &self.tracker_file {
+            trackers.extend(listing::parse_tracker_list(&std::fs::read_to_string(
+                tracker_file,
+            )?));
+        }
+
+        if trackers.is_empty() {
+            return Err(UsageError("no trackers given".into()).into());
+        }
+
+        Ok(trackers)
+    }
+
+    fn listing_options(&self) -> Result<ListingOptions, Box<dyn std::error::Error>> {
+        if self.connect_timeout == 0 || self.timeout == 0 {
+            return Err(UsageError("timeouts must be at least 1 second".into()).into());
+        }
+
+        Ok(ListingOptions {
+            password: self.password.clone(),
+            connect_timeout: Duration::from_secs(self.connect_timeout),
+            read_timeout: Duration::from_secs(self.timeout),
+            retries: self.retries,
+            ..Default::default()
+        })
+    }
+}
+
+// exit codes, so scripts can tell why a tracker couldn't be reached. clap also exits with 2 when
+// the arguments are wrong.
+const EXIT_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_RESOLVE: i32 = 3;
+const EXIT_CONNECT: i32 = 4;
+const EXIT_TIMEOUT: i32 = 5;
+const EXIT_NOT_A_TRACKER: i32 = 6;
+const EXIT_TRUNCATED: i32 = 7;
+const EXIT_NO_LISTING: i32 = 8;
+
+fn exit_code(err: &Error) -> i32 {
+    match err {
+        Error::InvalidAddress(_) => EXIT_USAGE,
+        Error::Resolve { .. } => EXIT_RESOLVE,
+        Error::Connect(_) | Error::Io(_) => EXIT_CONNECT,
+        Error::ConnectTimeout | Error::ReadTimeout => EXIT_TIMEOUT,
+        Error::InvalidHeader { .. } | Error::MissingHeader => EXIT_NOT_A_TRACKER,
+        Error::Truncated => EXIT_TRUNCATED,
+        Error::NoListing => EXIT_NO_LISTING,
+        Error::PasswordTooLong => EXIT_ERROR,
+    }
+}
+
+/// the arguments or config file are wrong
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+struct UsageError(String);
+
+/// none of the trackers worked. each of their errors has already been printed.
+#[derive(Debug, thiserror::Error)]
+#[error("{message}")]
+struct TrackersFailed {
+    message: &'static str,
+    exit_code: i32,
+}
+
+impl TrackersFailed {
+    /// if every tracker failed the same way, exit with that failure's code
+    fn new<'a>(message: &'static str, errors: impl IntoIterator<Item = &'a Error>) -> Self {
+        let mut codes = errors.into_iter().map(exit_code);
+        let first = codes.next().unwrap_or(EXIT_ERROR);
+        let exit_code = if codes.all(|code| code == first) {
+            first
+        } else {
+            EXIT_ERROR
+        };
+
+        Self { message, exit_code }
+    }
+}
 
 #[derive(Parser, Debug)]
 struct ListArgs {
-    /// The tracker to list servers from
-    tracker: String,
+    #[clap(flatten)]
+    trackers: TrackerArgs,
+
+    /// How to print the servers
+    #[clap(short, long, arg_enum, default_value = "text")]
+    format: OutputFormat,
+
+    /// Only show servers with this text in their name or description (ignoring case)
+    #[clap(short, long)]
+    search: Option<String>,
+
+    /// Only show servers whose name or description matches this regular expression
+    #[clap(long)]
+    regex: Option<regex::Regex>,
+
+    /// Only show servers with at least this many users online
+    #[clap(long)]
+    min_users: Option<u16>,
+
+    /// Only show servers with at most this many users online
+    #[clap(long)]
+    max_users: Option<u16>,
+
+    /// Only show servers whose address starts with this, like `192.168.`
+    #[clap(long)]
+    address: Option<String>,
+
+    /// Sort the servers. By default they're shown in the order the trackers listed them.
+    #[clap(long, arg_enum)]
+    sort: Option<SortKey>,
+
+    /// Reverse the sort order
+    #[clap(long, requires = "sort")]
+    reverse: bool,
+}
+
+#[derive(Parser, Debug)]
+struct BrowseArgs {
+    #[clap(flatten)]
+    trackers: TrackerArgs,
+
+    /// Seconds between refreshing the listing
+    #[clap(long, default_value = "60")]
+    refresh: u64,
+
+    /// Where bookmarks for the selected server are written
+    #[clap(long, default_value = ".")]
+    bookmark_dir: PathBuf,
+}
+
+#[derive(Parser, Debug)]
+struct WatchArgs {
+    #[clap(flatten)]
+    trackers: TrackerArgs,
+
+    /// Seconds between polls
+    #[clap(long, default_value = "60")]
+    interval: u64,
+
+    /// Print each change as a line of JSON
+    #[clap(long)]
+    json: bool,
 }
 
 #[derive(Parser, Debug)]
 struct RegisterArgs {
-    /// The tracker to register to
-    tracker: String,
+    /// The tracker to register to, as `host`, `host:port` or `[ipv6]:port`. Replaces the trackers
+    /// from the config file.
+    tracker: Option<String>,
+
+    /// A config file with the registration settings. Options given on the command line take
+    /// precedence over the file.
+    #[clap(short, long)]
+    config: Option<PathBuf>,
 
-    /// [Required] The name of your server; how it will appear in the tracker
+    /// Keep re-registering on an interval instead of registering once
+    #[clap(long)]
+    daemon: bool,
+
+    /// The name of your server; how it will appear in the tracker
     #[clap(short, long)]
-    name: String,
+    name: Option<String>,
 
-    /// [Required] How your server will be described in the listing
+    /// How your server will be described in the listing
     #[clap(short, long)]
-    description: String,
+    description: Option<String>,
 
-    /// The port for your Hotline server
-    #[clap(short, long, default_value = "5500")]
-    port: u16,
+    /// The port for your Hotline server [default: 5500]
+    #[clap(short, long)]
+    port: Option<u16>,
 
-    /// [Required] Number of users currently connected to this server.
-    #[clap(long, default_value = "0")]
-    user_count: u16,
+    /// Number of users currently connected to this server [default: 0]
+    #[clap(long)]
+    user_count: Option<u16>,
+
+    /// A command that prints the number of users currently connected to this server
+    #[clap(long)]
+    users_command: Option<String>,
 
-    /// [Required] The unique ID for this server
+    /// A file containing the number of users currently connected to this server
     #[clap(long)]
-    id: u32,
+    users_file: Option<PathBuf>,
 
-    #[clap(long, default_value = "")]
-    password: String,
+    /// The unique ID for this server. If it isn't given, one is generated the first time a server
+    /// registers and reused after that.
+    #[clap(long)]
+    id: Option<u32>,
+
+    /// Where generated server IDs are kept
+    /// [default: $XDG_STATE_HOME/hotline/server-ids.json or ~/.local/state/hotline/server-ids.json]
+    #[clap(long)]
+    state_file: Option<PathBuf>,
+
+    #[clap(long)]
+    password: Option<String>,
+
+    /// Seconds between registrations with --daemon [default: 120]
+    #[clap(long)]
+    interval: Option<u64>,
+
+    /// The local UDP port to send the registration from. By default, any free port is used.
+    #[clap(long, default_value = "0")]
+    local_port: u16,
 }
 
 #[derive(Parser, Debug)]
 enum Subcommand {
-    /// List servers on a given tracker
+    /// List servers on one or more trackers
     List(ListArgs),
 
-    /// Manually register your server to a tracker
+    /// Interactively browse the servers on one or more trackers
+    Browse(BrowseArgs),
+
+    /// Poll trackers and print servers that appear, disappear or change
+    Watch(WatchArgs),
+
+    /// Register your server to trackers, once or continuously
     Register(RegisterArgs),
 }
 
@@ -69,97 +286,194 @@ async fn main() {
 
     let result = match args.subcommand {
         Subcommand::List(list_args) => list_tracker(&list_args).await,
+        Subcommand::Browse(browse_args) => browse(browse_args).await,
+        Subcommand::Watch(watch_args) => watch(watch_args).await,
         Subcommand::Register(register_args) => register(&register_args).await,
     };
 
     if let Err(err) = result {
-        eprintln!("Error: {:?}", err);
-        std::process::exit(1);
+        eprintln!("Error: {err}");
+
+        let code = if let Some(err) = err.downcast_ref::<TrackersFailed>() {
+            err.exit_code
+        } else if let Some(err) = err.downcast_ref::<Error>() {
+            exit_code(err)
+        } else if err.is::<UsageError>() || err.is::<RegistrationConfigError>() {
+            EXIT_USAGE
+        } else {
+            EXIT_ERROR
+        };
+        std::process::exit(code);
     }
 }
 
 async fn list_tracker(args: &ListArgs) -> Result<(), Box<dyn std::error::Error>> {
-    // FIXME: this currently only works with default port
-    let mut client = Client::connect(&args.tracker, 5498).await?;
-
-    let mut last_update: Option<UpdateRecord> = None;
-    let mut servers = vec![];
-
-    while let Some(packet) = client.framed_stream.next().await {
-        match packet {
-            Ok(TrackerPacket::Update(update)) => {
-                last_update = Some(update);
-                // println!("got update:");
-                // println!("  users_online:  {}", update.users_online);
-                // println!("  total_servers: {}", update.total_servers);
-            }
-            Ok(TrackerPacket::Server(server)) => {
-                servers.push(server);
-                // println!("{} [{}:{}]", server.name, server.address, server.port);
-                // println!("  {}", server.description);
-            }
-            Ok(TrackerPacket::Header) => {
-                // eprintln!("connected!");
-            }
-            Ok(TrackerPacket::Complete) => {
-                break;
-            }
-            Err(err) => {
-                panic!("got something else: {err}");
-            }
-        }
+    let trackers = args.trackers.trackers()?;
+    let options = args.trackers.listing_options()?;
+
+    // a tracker that fails is reported, but doesn't stop us from listing the others.
+    let (listings, failures) = listing::fetch_all(&trackers, &options).await;
+
+    for (tracker, err) in &failures {
+        eprintln!("{tracker}: {err}");
     }
 
-    // ServerName                  [24.6.34.123:5500] (6 Users)
-    //   The best server ever in the history of Hotline
-    // Another crazy server      [142.33.94.120:5500] (0 Users)
-    //   Just another server tha tno one really cares about,
-    //   someone is bound to come check it out at some point.
+    if listings.is_empty() {
+        return Err(TrackersFailed::new(
+            "failed to list servers from any tracker",
+            failures.iter().map(|(_, err)| err),
+        )
+        .into());
+    }
+
+    let filter = ServerFilter {
+        search: args.search.clone(),
+        regex: args.regex.clone(),
+        min_users: args.min_users,
+        max_users: args.max_users,
+        address_prefix: args.address.clone(),
+    };
 
-    // now that this completed, let's print out all of the servers we got
-    for s in servers {
-        println!(
-            "{} [{}] ({} Users)",
-            bold(&s.name.as_string()),
-            s.address_with_port(),
-            s.users_online,
-        );
+    let mut servers = listing::merge(&listings);
+    servers.retain(|s| filter.matches(s));
+
+    if let Some(sort) = args.sort {
+        filter::sort_servers(&mut servers, sort, args.reverse);
     }
 
-    if let Some(last_update) = last_update {
-        // print the final update
-        println!();
-        println!("{} Total servers.", last_update.total_servers);
+    output::write_servers(
+        std::io::stdout().lock(),
+        args.format,
+        &servers,
+        listing::total_servers(&listings),
+    )
+}
+
+async fn browse(args: BrowseArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let trackers = args.trackers.trackers()?;
+
+    if args.refresh == 0 {
+        return Err("refresh interval must be at least 1 second".into());
     }
 
-    Ok(())
+    browse::run(
+        trackers,
+        args.trackers.listing_options()?,
+        Duration::from_secs(args.refresh),
+        args.bookmark_dir,
+    )
+    .await
 }
 
-fn bold(s: &str) -> String {
-    format!("{}{}{}", style::Bold, s, style::Reset)
+async fn watch(args: WatchArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let trackers = args.trackers.trackers()?;
+
+    if args.interval == 0 {
+        return Err("interval must be at least 1 second".into());
+    }
+
+    watch::run(
+        trackers,
+        args.trackers.listing_options()?,
+        Duration::from_secs(args.interval),
+        args.json,
+    )
+    .await
 }
 
 async fn register(args: &RegisterArgs) -> Result<(), Box<dyn std::error::Error>> {
-    let name = MacRomanString::from(args.name.as_str());
-    let description = MacRomanString::from(args.description.as_str());
-    let password = MacRomanString::from(args.password.as_str());
-
-    // create the registration record so we have something to send.
-    let r = RegistrationRecord {
-        port: args.port,
-        users_online: args.user_count,
-        id: args.id,
-        name,
-        description,
-        password,
-        ..RegistrationRecord::default()
+    let mut config = match &args.config {
+        Some(path) => RegistrationConfig::load(path).map_err(|err| UsageError(err.to_string()))?,
+        None => RegistrationConfig::default(),
     };
 
-    // fling out a UDP packet to the tracker server.
-    let socket = UdpSocket::bind("0.0.0.0:2000").await?;
-    let buf = r.to_bytes();
-    let addr = format!("{}:5499", &args.tracker);
-    socket.send_to(&buf, &addr).await?;
+    if let Some(tracker) = &args.tracker {
+        config.trackers = vec![tracker.clone()];
+    }
+
+    // a users source on the command line replaces whichever one the config file has
+    if args.user_count.is_some() || args.users_command.is_some() || args.users_file.is_some() {
+        config.users_online = args.user_count;
+        config.users_command = args.users_command.clone();
+        config.users_file = args.users_file.clone();
+    }
+
+    config.name = args.name.clone().or(config.name);
+    config.description = args.description.clone().or(config.description);
+    config.port = args.port.or(config.port);
+    config.id = args.id.or(config.id);
+
+    // servers without an id get a stable one generated for them
+    if config.id.is_none() {
+        if let (Some(name), Some(state_file)) = (
+            &config.name,
+            args.state_file
+                .clone()
+                .or_else(server_id::default_state_file),
+        ) {
+            config.id = Some(server_id::load_or_create(
+                &state_file,
+                name,
+                config.port.unwrap_or(registration::DEFAULT_SERVER_PORT),
+            )?);
+        }
+    }
+    config.password = args.password.clone().or(config.password);
+    config.interval = args.interval.or(config.interval);
+
+    let registration = config.into_registration()?;
+
+    if args.daemon {
+        let env = Env::default().filter_or("TRACKER_LOG_LEVEL", "info");
+        Builder::from_env(env).init();
+
+        return registration::run_daemon(registration, args.local_port).await;
+    }
+
+    // fling out a UDP packet to each tracker.
+    let users_online = registration.users.users_online().await?;
+    let mut failures = vec![];
+    for tracker in &registration.trackers {
+        let record = RegistrationRecord {
+            users_online,
+            ..registration.record.clone()
+        };
+
+        let options = RegisterOptions {
+            local_port: args.local_port,
+            ..Default::default()
+        };
+
+        if let Err(err) = register_with(tracker, &record, &options).await {
+            eprintln!("{tracker}: {err}");
+            failures.push(err);
+        }
+    }
+
+    if !failures.is_empty() {
+        return Err(TrackersFailed::new("failed to register with some trackers", &failures).into());
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::str::FromStr;
+
+    #[test]
+    fn it_picks_an_exit_code() {
+        let timeouts = [Error::ConnectTimeout, Error::ReadTimeout];
+        assert_eq!(TrackersFailed::new("", &timeouts).exit_code, EXIT_TIMEOUT);
+
+        let mixed = [Error::ConnectTimeout, Error::NoListing];
+        assert_eq!(TrackersFailed::new("", &mixed).exit_code, EXIT_ERROR);
+
+        let bad_address = hotline_tracker_client::TrackerAddress::from_str("foo:bar:5498")
+            .unwrap_err()
+            .into();
+        assert_eq!(exit_code(&bad_address), EXIT_USAGE);
+    }
+}
@@ -0,0 +1,208 @@
+use std::io::Write;
+use std::net::Ipv4Addr;
+
+use hotline_tracker::ServerRecord;
+
+use serde::Serialize;
+
+use termion::style;
+
+/// how `list` prints the servers it receives
+#[derive(clap::ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// human readable text
+    Text,
+
+    /// a single JSON array of servers
+    Json,
+
+    /// one JSON object per line
+    Jsonl,
+
+    /// comma-separated values with a header row
+    Csv,
+
+    /// tab-separated values with a header row
+    Tsv,
+}
+
+/// a server from a tracker listing, along with the trackers it was listed on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ListedServer {
+    pub trackers: Vec<String>,
+    pub address: Ipv4Addr,
+    pub port: u16,
+    pub users_online: u16,
+    pub reserved: u16,
+    pub name: String,
+    pub description: String,
+}
+
+impl ListedServer {
+    pub fn new(tracker: &str, server: &ServerRecord) -> Self {
+        Self {
+            trackers: vec![tracker.into()],
+            address: server.address,
+            port: server.port,
+            users_online: server.users_online,
+            reserved: server.reserved,
+            name: server.name.as_string(),
+            description: server.description.as_string(),
+        }
+    }
+
+    pub fn address_with_port(&self) -> String {
+        format!("{}:{}", self.address, self.port)
+    }
+}
+
+/// csv can't hold a list in a single column, so the trackers are joined with spaces
+#[derive(Serialize)]
+struct FlatServer<'a> {
+    trackers: String,
+    address: Ipv4Addr,
+    port: u16,
+    users_online: u16,
+    reserved: u16,
+    name: &'a str,
+    description: &'a str,
+}
+
+impl<'a> From<&'a ListedServer> for FlatServer<'a> {
+    fn from(s: &'a ListedServer) -> Self {
+        Self {
+            trackers: s.trackers.join(" "),
+            address: s.address,
+            port: s.port,
+            users_online: s.users_online,
+            reserved: s.reserved,
+            name: &s.name,
+            description: &s.description,
+        }
+    }
+}
+
+/// write out the servers in the given format. Text ends with `total_servers`, the count the
+/// trackers reported, if they sent one.
+pub fn write_servers<W: Write>(
+    mut out: W,
+    format: OutputFormat,
+    servers: &[ListedServer],
+    total_servers: Option<u32>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        OutputFormat::Text => {
+            // ServerName                  [24.6.34.123:5500] (6 Users)
+            for s in servers {
+                writeln!(
+                    out,
+                    "{} [{}] ({} Users)",
+                    bold(&s.name),
+                    s.address_with_port(),
+                    s.users_online,
+                )?;
+            }
+
+            if let Some(total_servers) = total_servers {
+                writeln!(out)?;
+                writeln!(out, "{total_servers} Total servers.")?;
+            }
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, servers)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Jsonl => {
+            for s in servers {
+                serde_json::to_writer(&mut out, s)?;
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let delimiter = if format == OutputFormat::Csv {
+                b','
+            } else {
+                b'\t'
+            };
+
+            let mut writer = csv::WriterBuilder::new()
+                .delimiter(delimiter)
+                .from_writer(out);
+
+            for s in servers {
+                writer.serialize(FlatServer::from(s))?;
+            }
+
+            writer.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+fn bold(s: &str) -> String {
+    format!("{}{}{}", style::Bold, s, style::Reset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn servers() -> Vec<ListedServer> {
+        let server = ServerRecord {
+            name: "Hotline, Central".into(),
+            description: "a \"quoted\" server".into(),
+            users_online: 3,
+            ..Default::default()
+        };
+
+        let mut server = ListedServer::new("tracker.example.com", &server);
+        server.trackers.push("hltracker.com".into());
+
+        vec![server]
+    }
+
+    fn render(format: OutputFormat) -> String {
+        let mut out = vec![];
+        write_servers(&mut out, format, &servers(), Some(12)).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn it_writes_text() {
+        let text = render(OutputFormat::Text);
+        assert!(text.contains("Hotline, Central"));
+        assert!(text.contains("[127.0.0.1:5500] (3 Users)"));
+
+        // the total is the trackers', not how many servers were written
+        assert!(text.ends_with("\n\n12 Total servers.\n"));
+    }
+
+    #[test]
+    fn it_writes_csv_and_tsv() {
+        assert_eq!(
+            render(OutputFormat::Csv),
+            "trackers,address,port,users_online,reserved,name,description\n\
+             tracker.example.com hltracker.com,127.0.0.1,5500,3,0,\"Hotline, Central\",\"a \"\"quoted\"\" server\"\n"
+        );
+
+        assert_eq!(
+            render(OutputFormat::Tsv),
+            "trackers\taddress\tport\tusers_online\treserved\tname\tdescription\n\
+             tracker.example.com hltracker.com\t127.0.0.1\t5500\t3\t0\tHotline, Central\t\"a \"\"quoted\"\" server\"\n"
+        );
+    }
+
+    #[test]
+    fn it_writes_json() {
+        let json: serde_json::Value = serde_json::from_str(&render(OutputFormat::Json)).unwrap();
+        assert_eq!(json[0]["trackers"][1], "hltracker.com");
+        assert_eq!(json[0]["name"], "Hotline, Central");
+        assert_eq!(json[0]["port"], 5500);
+
+        let jsonl = render(OutputFormat::Jsonl);
+        assert_eq!(jsonl.lines().count(), 1);
+        let json: serde_json::Value = serde_json::from_str(jsonl.trim()).unwrap();
+        assert_eq!(json["address"], "127.0.0.1");
+    }
+}
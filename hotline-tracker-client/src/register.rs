@@ -0,0 +1,108 @@
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use tokio::net::UdpSocket;
+use tokio::time::{timeout, Duration};
+
+use hotline_tracker::RegistrationRecord;
+
+use crate::fetch::DEFAULT_TIMEOUT;
+use crate::tracker_address::{TrackerAddress, DEFAULT_REGISTRATION_PORT};
+use crate::Error;
+
+/// settings for sending a registration
+#[derive(Debug, Clone)]
+pub struct RegisterOptions {
+    /// the local UDP port to send from. 0 picks any free port.
+    pub local_port: u16,
+
+    /// how long to spend looking up the tracker and sending the registration
+    pub timeout: Duration,
+}
+
+impl Default for RegisterOptions {
+    fn default() -> Self {
+        Self {
+            local_port: 0,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+/// register a server with a tracker. Registrations are a single UDP packet, so this can't tell
+/// whether the tracker accepted it.
+pub async fn register(addr: &TrackerAddress, record: &RegistrationRecord) -> Result<(), Error> {
+    register_with(addr, record, &RegisterOptions::default()).await
+}
+
+/// register a server with a tracker with the given options. If the tracker's hostname has several
+/// addresses, each of them gets the registration, and it's only an error if none of them could be
+/// sent to.
+pub async fn register_with(
+    addr: &TrackerAddress,
+    record: &RegistrationRecord,
+    options: &RegisterOptions,
+) -> Result<(), Error> {
+    let send = async {
+        let addrs = addr.resolve(DEFAULT_REGISTRATION_PORT).await?;
+        send_to_all(&addrs, &record.to_bytes(), options.local_port).await
+    };
+
+    timeout(options.timeout, send)
+        .await
+        .map_err(|_| Error::ConnectTimeout)?
+}
+
+/// send `buf` to every address, carrying on past any that fail. It's only an error if none of the
+/// sends worked, in which case the last failure is returned.
+async fn send_to_all(addrs: &[SocketAddr], buf: &[u8], local_port: u16) -> Result<(), Error> {
+    let mut result = Ok(());
+    let mut sent = false;
+
+    for &addr in addrs {
+        let local_addr: SocketAddr = if addr.is_ipv4() {
+            (Ipv4Addr::UNSPECIFIED, local_port).into()
+        } else {
+            (Ipv6Addr::UNSPECIFIED, local_port).into()
+        };
+
+        let send = async {
+            let socket = UdpSocket::bind(local_addr).await?;
+            socket.send_to(buf, addr).await
+        };
+
+        match send.await {
+            Ok(_) => sent = true,
+            Err(err) => result = Err(Error::Io(err)),
+        }
+    }
+
+    if sent {
+        Ok(())
+    } else {
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_sends_to_every_address() {
+        let tracker = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let tracker_addr = tracker.local_addr().unwrap();
+
+        // nothing can be sent to port 0
+        let bad: SocketAddr = "127.0.0.1:0".parse().unwrap();
+
+        send_to_all(&[bad, tracker_addr], b"hello", 0)
+            .await
+            .unwrap();
+
+        let mut buf = [0; 5];
+        let (len, _) = tracker.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], b"hello");
+
+        assert!(send_to_all(&[bad], b"hello", 0).await.is_err());
+    }
+}
@@ -0,0 +1,358 @@
+use std::path::{Path, PathBuf};
+
+use tokio::time::{interval, Duration, MissedTickBehavior};
+
+use hotline_tracker::RegistrationRecord;
+use hotline_tracker_client::tracker_address::InvalidTrackerAddress;
+use hotline_tracker_client::{register_with, RegisterOptions, TrackerAddress};
+use macroman_tools::MacRomanString;
+
+use serde::Deserialize;
+
+use thiserror::Error;
+
+use log::{info, warn};
+
+/// trackers forget servers after five minutes, so re-register well before then in case a packet
+/// gets lost
+pub const DEFAULT_REGISTRATION_INTERVAL: u64 = 120;
+
+pub const DEFAULT_SERVER_PORT: u16 = 5500;
+
+#[derive(Debug, Error)]
+pub enum RegistrationConfigError {
+    #[error("No trackers to register with")]
+    MissingTrackers,
+
+    #[error("Missing {0}")]
+    MissingField(&'static str),
+
+    #[error("Only one of users-online, users-command and users-file can be set")]
+    ConflictingUsersSource,
+
+    #[error("Interval must be at least 1 second")]
+    InvalidInterval,
+
+    #[error(transparent)]
+    InvalidTracker(#[from] InvalidTrackerAddress),
+}
+
+/// registration settings, from a config file and/or the command line
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RegistrationConfig {
+    #[serde(default)]
+    pub trackers: Vec<String>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub port: Option<u16>,
+    pub id: Option<u32>,
+    pub password: Option<String>,
+
+    /// a fixed number of users online
+    pub users_online: Option<u16>,
+
+    /// a shell command that prints the number of users online
+    pub users_command: Option<String>,
+
+    /// a file containing the number of users online
+    pub users_file: Option<PathBuf>,
+
+    /// seconds between registrations in daemon mode
+    pub interval: Option<u64>,
+}
+
+impl RegistrationConfig {
+    /// load a config file. A relative `users-file` is relative to the config file.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let data = std::fs::read_to_string(path)?;
+        let mut config: Self = toml::from_str(&data)?;
+
+        if let (Some(users_file), Some(base_path)) = (&config.users_file, path.parent()) {
+            config.users_file = Some(base_path.join(users_file));
+        }
+
+        Ok(config)
+    }
+
+    /// check that everything needed to register is here
+    pub fn into_registration(self) -> Result<Registration, RegistrationConfigError> {
+        if self.trackers.is_empty() {
+            return Err(RegistrationConfigError::MissingTrackers);
+        }
+
+        let trackers = self
+            .trackers
+            .iter()
+            .map(|tracker| tracker.parse())
+            .collect::<Result<Vec<TrackerAddress>, _>>()?;
+
+        let users = match (self.users_online, self.users_command, self.users_file) {
+            (users, None, None) => UsersSource::Fixed(users.unwrap_or(0)),
+            (None, Some(command), None) => UsersSource::Command(command),
+            (None, None, Some(file)) => UsersSource::File(file),
+            _ => return Err(RegistrationConfigError::ConflictingUsersSource),
+        };
+
+        let interval = self.interval.unwrap_or(DEFAULT_REGISTRATION_INTERVAL);
+        if interval == 0 {
+            return Err(RegistrationConfigError::InvalidInterval);
+        }
+
+        let record = RegistrationRecord {
+            port: self.port.unwrap_or(DEFAULT_SERVER_PORT),
+            id: self.id.ok_or(RegistrationConfigError::MissingField("id"))?,
+            name: MacRomanString::from(
+                self.name
+                    .ok_or(RegistrationConfigError::MissingField("name"))?
+                    .as_str(),
+            ),
+            description: MacRomanString::from(
+                self.description
+                    .ok_or(RegistrationConfigError::MissingField("description"))?
+                    .as_str(),
+            ),
+            password: MacRomanString::from(self.password.unwrap_or_default().as_str()),
+            ..RegistrationRecord::default()
+        };
+
+        Ok(Registration {
+            trackers,
+            record,
+            users,
+            interval: Duration::from_secs(interval),
+        })
+    }
+}
+
+/// where the number of users online comes from
+#[derive(Debug, PartialEq, Eq)]
+pub enum UsersSource {
+    Fixed(u16),
+    Command(String),
+    File(PathBuf),
+}
+
+impl UsersSource {
+    pub async fn users_online(&self) -> Result<u16, Box<dyn std::error::Error>> {
+        let output = match self {
+            UsersSource::Fixed(users) => return Ok(*users),
+            UsersSource::Command(command) => {
+                let output = tokio::process::Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .output()
+                    .await?;
+
+                if !output.status.success() {
+                    return Err(format!("users command failed: {}", output.status).into());
+                }
+
+                String::from_utf8(output.stdout)?
+            }
+            UsersSource::File(path) => tokio::fs::read_to_string(path).await?,
+        };
+
+        output
+            .trim()
+            .parse()
+            .map_err(|_| format!("not a number of users: {:?}", output.trim()).into())
+    }
+}
+
+/// a server's registration, ready to send
+#[derive(Debug)]
+pub struct Registration {
+    pub trackers: Vec<TrackerAddress>,
+    pub record: RegistrationRecord,
+    pub users: UsersSource,
+    pub interval: Duration,
+}
+
+/// register with every tracker once. Returns how many registrations were sent successfully;
+/// failures are logged.
+pub async fn register_all(
+    registration: &Registration,
+    users_online: u16,
+    local_port: u16,
+) -> usize {
+    let record = RegistrationRecord {
+        users_online,
+        ..registration.record.clone()
+    };
+    let options = RegisterOptions {
+        local_port,
+        ..Default::default()
+    };
+
+    let results = futures::future::join_all(
+        registration
+            .trackers
+            .iter()
+            .map(|tracker| register_with(tracker, &record, &options)),
+    )
+    .await;
+
+    let mut sent = 0;
+    for (tracker, result) in registration.trackers.iter().zip(results) {
+        match result {
+            Ok(()) => sent += 1,
+            Err(err) => warn!("Failed to register with {tracker}: {err}"),
+        }
+    }
+
+    sent
+}
+
+/// keep re-registering until interrupted
+pub async fn run_daemon(
+    registration: Registration,
+    local_port: u16,
+) -> Result<(), Box<dyn std::error::Error>> {
+    info!(
+        "Registering {} with {} tracker(s) every {}s",
+        registration.record.name,
+        registration.trackers.len(),
+        registration.interval.as_secs()
+    );
+
+    let mut ticks = interval(registration.interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    // if the user count can't be read, keep sending the last one we knew about
+    let mut users_online = 0;
+
+    loop {
+        tokio::select! {
+            _ = ticks.tick() => {}
+            _ = tokio::signal::ctrl_c() => {
+                info!("Stopping.");
+                return Ok(());
+            }
+        }
+
+        match registration.users.users_online().await {
+            Ok(users) => users_online = users,
+            Err(err) => warn!("Failed to get the number of users online: {err}"),
+        }
+
+        let sent = register_all(&registration, users_online, local_port).await;
+        info!(
+            "Registered with {sent}/{} tracker(s), {users_online} users online",
+            registration.trackers.len()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::net::UdpSocket;
+
+    #[test]
+    fn it_loads_a_registration_config() {
+        let config: RegistrationConfig = toml::from_str(
+            r#"
+            trackers = ["hltracker.com", "tracker.example.com:6499"]
+            name = "My Server"
+            description = "A server"
+            id = 1234
+            users-file = "/var/run/hotline/users"
+            interval = 60
+            "#,
+        )
+        .unwrap();
+
+        let registration = config.into_registration().unwrap();
+        assert_eq!(registration.trackers.len(), 2);
+        assert_eq!(registration.trackers[1].port, Some(6499));
+        assert_eq!(registration.record.name.as_string(), "My Server");
+        assert_eq!(registration.record.port, 5500);
+        assert_eq!(registration.record.id, 1234);
+        assert_eq!(
+            registration.users,
+            UsersSource::File("/var/run/hotline/users".into())
+        );
+        assert_eq!(registration.interval, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn it_requires_the_basics() {
+        let config = RegistrationConfig {
+            trackers: vec!["hltracker.com".into()],
+            name: Some("My Server".into()),
+            description: Some("A server".into()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.into_registration(),
+            Err(RegistrationConfigError::MissingField("id"))
+        ));
+
+        let config = RegistrationConfig {
+            trackers: vec!["hltracker.com".into()],
+            name: Some("My Server".into()),
+            description: Some("A server".into()),
+            id: Some(1),
+            users_online: Some(3),
+            users_command: Some("echo 3".into()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.into_registration(),
+            Err(RegistrationConfigError::ConflictingUsersSource)
+        ));
+
+        assert!(matches!(
+            RegistrationConfig::default().into_registration(),
+            Err(RegistrationConfigError::MissingTrackers)
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_reads_users_online() {
+        assert_eq!(UsersSource::Fixed(4).users_online().await.unwrap(), 4);
+        assert_eq!(
+            UsersSource::Command("echo 12".into())
+                .users_online()
+                .await
+                .unwrap(),
+            12
+        );
+        assert!(UsersSource::Command("echo lots".into())
+            .users_online()
+            .await
+            .is_err());
+        assert!(UsersSource::Command("exit 1".into())
+            .users_online()
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn it_sends_registrations() {
+        let tracker = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address: TrackerAddress = tracker.local_addr().unwrap().to_string().parse().unwrap();
+
+        let registration = Registration {
+            trackers: vec![address, "127.0.0.1:0".parse().unwrap()],
+            record: RegistrationRecord {
+                id: 77,
+                name: "My Server".into(),
+                ..Default::default()
+            },
+            users: UsersSource::Fixed(0),
+            interval: Duration::from_secs(1),
+        };
+
+        // nothing can be sent to the second tracker, which shouldn't stop the first
+        assert_eq!(register_all(&registration, 5, 0).await, 1);
+
+        let mut buf = [0u8; 1024];
+        let len = tracker.recv(&mut buf).await.unwrap();
+        let record = RegistrationRecord::from_bytes(&buf[..len]).unwrap();
+        assert_eq!(record.id, 77);
+        assert_eq!(record.users_online, 5);
+    }
+}
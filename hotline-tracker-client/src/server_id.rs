@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// ids are remembered in here, relative to the state directory
+pub const STATE_FILENAME: &str = "hotline/server-ids.json";
+
+/// where generated server ids are kept: `$XDG_STATE_HOME/hotline/server-ids.json`, or
+/// `~/.local/state/hotline/server-ids.json` if that isn't set.
+pub fn default_state_file() -> Option<PathBuf> {
+    if let Some(state_home) = std::env::var_os("XDG_STATE_HOME").filter(|s| !s.is_empty()) {
+        return Some(PathBuf::from(state_home).join(STATE_FILENAME));
+    }
+
+    let home = std::env::var_os("HOME").filter(|s| !s.is_empty())?;
+    Some(
+        PathBuf::from(home)
+            .join(".local/state")
+            .join(STATE_FILENAME),
+    )
+}
+
+/// the same server keeps the same id, so it replaces its own listing instead of showing up twice
+fn key(name: &str, port: u16) -> String {
+    format!("{name}:{port}")
+}
+
+/// look up the id for a server in the state file, generating and saving a new one if this server
+/// hasn't registered before
+pub fn load_or_create(
+    path: &Path,
+    name: &str,
+    port: u16,
+) -> Result<u32, Box<dyn std::error::Error>> {
+    let mut ids: BTreeMap<String, u32> = match std::fs::read_to_string(path) {
+        Ok(data) => {
+            serde_json::from_str(&data).map_err(|err| format!("{}: {err}", path.display()))?
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(err) => return Err(err.into()),
+    };
+
+    if let Some(id) = ids.get(&key(name, port)) {
+        return Ok(*id);
+    }
+
+    // 0 looks like an id that was never set, so avoid it
+    let id = loop {
+        let id: u32 = rand::random();
+        if id != 0 && !ids.values().any(|&existing| existing == id) {
+            break id;
+        }
+    };
+    ids.insert(key(name, port), id);
+
+    // write to a temporary file first so a crash can't leave a half-written state file behind
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, serde_json::to_string_pretty(&ids)?)?;
+    std::fs::rename(&tmp_path, path)?;
+
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_remembers_generated_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state/server-ids.json");
+
+        let id = load_or_create(&path, "My Server", 5500).unwrap();
+        assert_ne!(id, 0);
+        assert_eq!(load_or_create(&path, "My Server", 5500).unwrap(), id);
+
+        // another server (or the same name on another port) gets its own id
+        let other = load_or_create(&path, "My Server", 5600).unwrap();
+        assert_ne!(other, id);
+        assert_eq!(load_or_create(&path, "My Server", 5500).unwrap(), id);
+    }
+}
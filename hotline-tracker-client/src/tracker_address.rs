@@ -0,0 +1,176 @@
+use std::fmt;
+use std::net::{Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+
+use tokio::net::lookup_host;
+
+use thiserror::Error;
+
+/// the port trackers list servers on (TCP)
+pub const DEFAULT_TRACKER_PORT: u16 = 5498;
+
+/// the port trackers accept server registrations on (UDP)
+pub const DEFAULT_REGISTRATION_PORT: u16 = 5499;
+
+#[derive(Debug, Error)]
+#[error("Invalid tracker address: {0}")]
+pub struct InvalidTrackerAddress(String);
+
+/// a tracker given on the command line: `host`, `host:port`, `ipv6`, or `[ipv6]:port`. When the
+/// port is left off, the default port for whatever we're doing is used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackerAddress {
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl TrackerAddress {
+    /// look up every address for this tracker. A hostname can resolve to several addresses.
+    pub async fn resolve(&self, default_port: u16) -> Result<Vec<SocketAddr>, crate::Error> {
+        let port = self.port.unwrap_or(default_port);
+        let resolve_error = |source| crate::Error::Resolve {
+            host: self.host.clone(),
+            source,
+        };
+
+        let addrs: Vec<SocketAddr> = lookup_host((self.host.as_str(), port))
+            .await
+            .map_err(resolve_error)?
+            .collect();
+
+        if addrs.is_empty() {
+            return Err(resolve_error(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "no addresses found",
+            )));
+        }
+
+        Ok(addrs)
+    }
+}
+
+impl FromStr for TrackerAddress {
+    type Err = InvalidTrackerAddress;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidTrackerAddress(s.into());
+        let parse_port = |port: &str| port.parse::<u16>().map_err(|_| invalid());
+
+        let (host, port) = if let Some(rest) = s.strip_prefix('[') {
+            // [ipv6] or [ipv6]:port
+            let (host, rest) = rest.split_once(']').ok_or_else(invalid)?;
+            host.parse::<Ipv6Addr>().map_err(|_| invalid())?;
+
+            let port = match rest {
+                "" => None,
+                rest => Some(parse_port(rest.strip_prefix(':').ok_or_else(invalid)?)?),
+            };
+
+            (host, port)
+        } else if s.parse::<Ipv6Addr>().is_ok() {
+            // a bare IPv6 address can't have a port
+            (s, None)
+        } else {
+            let (host, port) = match s.rsplit_once(':') {
+                Some((host, port)) => (host, Some(parse_port(port)?)),
+                None => (s, None),
+            };
+
+            // only IPv6 addresses can have colons in them, and those were handled above
+            if host.contains(':') {
+                return Err(invalid());
+            }
+
+            (host, port)
+        };
+
+        if host.is_empty() || host.contains(char::is_whitespace) {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            host: host.into(),
+            port,
+        })
+    }
+}
+
+impl fmt::Display for TrackerAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]", self.host)?;
+        } else {
+            write!(f, "{}", self.host)?;
+        }
+
+        if let Some(port) = self.port {
+            write!(f, ":{port}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> TrackerAddress {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn it_parses_tracker_addresses() {
+        assert_eq!(
+            parse("hltracker.com"),
+            TrackerAddress {
+                host: "hltracker.com".into(),
+                port: None
+            }
+        );
+        assert_eq!(parse("hltracker.com:6498").port, Some(6498));
+        assert_eq!(parse("192.0.2.1:5498").host, "192.0.2.1");
+        assert_eq!(parse("2001:db8::1").host, "2001:db8::1");
+        assert_eq!(parse("2001:db8::1").port, None);
+        assert_eq!(parse("[2001:db8::1]").port, None);
+        assert_eq!(
+            parse("[2001:db8::1]:6498"),
+            TrackerAddress {
+                host: "2001:db8::1".into(),
+                port: Some(6498)
+            }
+        );
+
+        assert!("".parse::<TrackerAddress>().is_err());
+        assert!("hltracker.com:".parse::<TrackerAddress>().is_err());
+        assert!("hltracker.com:99999".parse::<TrackerAddress>().is_err());
+        assert!("[hltracker.com]:5498".parse::<TrackerAddress>().is_err());
+        assert!("[2001:db8::1]5498".parse::<TrackerAddress>().is_err());
+        assert!("foo:bar:5498".parse::<TrackerAddress>().is_err());
+    }
+
+    #[test]
+    fn it_displays_tracker_addresses() {
+        assert_eq!(parse("hltracker.com").to_string(), "hltracker.com");
+        assert_eq!(
+            parse("[2001:db8::1]:6498").to_string(),
+            "[2001:db8::1]:6498"
+        );
+        assert_eq!(parse("2001:db8::1").to_string(), "[2001:db8::1]");
+    }
+
+    #[tokio::test]
+    async fn it_resolves_with_the_default_port() {
+        let addrs = parse("127.0.0.1")
+            .resolve(DEFAULT_TRACKER_PORT)
+            .await
+            .unwrap();
+        assert_eq!(addrs, vec!["127.0.0.1:5498".parse().unwrap()]);
+
+        let addrs = parse("[::1]:6498")
+            .resolve(DEFAULT_TRACKER_PORT)
+            .await
+            .unwrap();
+        assert_eq!(addrs, vec!["[::1]:6498".parse().unwrap()]);
+    }
+}
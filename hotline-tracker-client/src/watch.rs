@@ -0,0 +1,283 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+use tokio::time::{interval, Duration, MissedTickBehavior};
+
+use serde::Serialize;
+
+use hotline_tracker_client::ListingOptions;
+
+use crate::listing;
+use crate::output::ListedServer;
+
+/// something that happened to a server between two polls
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ServerEvent {
+    Appeared {
+        server: ListedServer,
+    },
+    Disappeared {
+        server: ListedServer,
+    },
+    Changed {
+        server: ListedServer,
+        changes: Vec<Change>,
+    },
+}
+
+/// a field of a server that changed, with its old and new values
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "field", rename_all = "snake_case")]
+pub enum Change {
+    Name { old: String, new: String },
+    Description { old: String, new: String },
+    UsersOnline { old: u16, new: u16 },
+}
+
+impl std::fmt::Display for ServerEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServerEvent::Appeared { server } => write!(
+                f,
+                "+ {} [{}] ({} Users)",
+                server.name,
+                server.address_with_port(),
+                server.users_online
+            ),
+            ServerEvent::Disappeared { server } => {
+                write!(f, "- {} [{}]", server.name, server.address_with_port())
+            }
+            ServerEvent::Changed { server, changes } => {
+                write!(f, "~ {} [{}]:", server.name, server.address_with_port())?;
+
+                for (i, change) in changes.iter().enumerate() {
+                    let separator = if i == 0 { " " } else { ", " };
+                    match change {
+                        Change::Name { old, .. } => write!(f, "{separator}renamed from {old:?}")?,
+                        Change::Description { new, .. } => {
+                            write!(f, "{separator}description is now {new:?}")?
+                        }
+                        Change::UsersOnline { old, new } => {
+                            write!(f, "{separator}users {old} -> {new}")?
+                        }
+                    }
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// compare two listings. Servers are matched up by address and port.
+pub fn diff(old: &[ListedServer], new: &[ListedServer]) -> Vec<ServerEvent> {
+    let old_servers: HashMap<String, &ListedServer> =
+        old.iter().map(|s| (s.address_with_port(), s)).collect();
+    let new_servers: HashSet<String> = new.iter().map(ListedServer::address_with_port).collect();
+
+    let mut events = vec![];
+
+    for server in new {
+        let old_server = match old_servers.get(&server.address_with_port()) {
+            Some(old_server) => old_server,
+            None => {
+                events.push(ServerEvent::Appeared {
+                    server: server.clone(),
+                });
+                continue;
+            }
+        };
+
+        let mut changes = vec![];
+        if old_server.name != server.name {
+            changes.push(Change::Name {
+                old: old_server.name.clone(),
+                new: server.name.clone(),
+            });
+        }
+        if old_server.description != server.description {
+            changes.push(Change::Description {
+                old: old_server.description.clone(),
+                new: server.description.clone(),
+            });
+        }
+        if old_server.users_online != server.users_online {
+            changes.push(Change::UsersOnline {
+                old: old_server.users_online,
+                new: server.users_online,
+            });
+        }
+
+        if !changes.is_empty() {
+            events.push(ServerEvent::Changed {
+                server: server.clone(),
+                changes,
+            });
+        }
+    }
+
+    for server in old {
+        if !new_servers.contains(&server.address_with_port()) {
+            events.push(ServerEvent::Disappeared {
+                server: server.clone(),
+            });
+        }
+    }
+
+    events
+}
+
+/// servers that were only listed by trackers we couldn't reach this time are carried over from the
+/// last poll, so an unreachable tracker doesn't look like all of its servers going away
+fn carry_over(old: &[ListedServer], new: &mut Vec<ListedServer>, failed: &HashSet<String>) {
+    let listed: HashSet<String> = new.iter().map(ListedServer::address_with_port).collect();
+
+    for server in old {
+        if !listed.contains(&server.address_with_port())
+            && server.trackers.iter().all(|t| failed.contains(t))
+        {
+            new.push(server.clone());
+        }
+    }
+}
+
+/// poll the trackers until interrupted, printing what changed each time
+pub async fn run(
+    trackers: Vec<String>,
+    options: ListingOptions,
+    poll_interval: Duration,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut ticks = interval(poll_interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut last: Option<Vec<ListedServer>> = None;
+
+    loop {
+        tokio::select! {
+            _ = ticks.tick() => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+
+        let (listings, failures) = listing::fetch_all(&trackers, &options).await;
+
+        for (tracker, err) in &failures {
+            eprintln!("{tracker}: {err}");
+        }
+
+        if listings.is_empty() {
+            // nothing to compare against; try again next time
+            continue;
+        }
+
+        let mut servers = listing::merge(&listings);
+
+        let last = match &mut last {
+            Some(last) => last,
+            None => {
+                // the first listing is what later ones are compared to
+                if !json {
+                    println!("Watching {} servers.", servers.len());
+                }
+                last = Some(servers);
+                continue;
+            }
+        };
+
+        let failed: HashSet<String> = failures.into_iter().map(|(tracker, _)| tracker).collect();
+        carry_over(last, &mut servers, &failed);
+
+        let mut out = std::io::stdout().lock();
+        for event in diff(last, &servers) {
+            if json {
+                serde_json::to_writer(&mut out, &event)?;
+                writeln!(out)?;
+            } else {
+                writeln!(out, "{event}")?;
+            }
+        }
+        out.flush()?;
+
+        *last = servers;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hotline_tracker::ServerRecord;
+
+    fn server(port: u16, name: &str, users_online: u16) -> ListedServer {
+        let record = ServerRecord {
+            port,
+            name: name.into(),
+            users_online,
+            ..Default::default()
+        };
+
+        ListedServer::new("tracker", &record)
+    }
+
+    #[test]
+    fn it_diffs_listings() {
+        let old = vec![
+            server(5500, "one", 1),
+            server(5501, "two", 2),
+            server(5502, "three", 3),
+        ];
+        let new = vec![
+            server(5500, "one", 1),
+            server(5501, "TWO", 5),
+            server(5503, "four", 0),
+        ];
+
+        let events = diff(&old, &new);
+        assert_eq!(
+            events,
+            vec![
+                ServerEvent::Changed {
+                    server: server(5501, "TWO", 5),
+                    changes: vec![
+                        Change::Name {
+                            old: "two".into(),
+                            new: "TWO".into()
+                        },
+                        Change::UsersOnline { old: 2, new: 5 },
+                    ],
+                },
+                ServerEvent::Appeared {
+                    server: server(5503, "four", 0)
+                },
+                ServerEvent::Disappeared {
+                    server: server(5502, "three", 3)
+                },
+            ]
+        );
+
+        assert_eq!(
+            events[0].to_string(),
+            "~ TWO [127.0.0.1:5501]: renamed from \"two\", users 2 -> 5"
+        );
+
+        let json = serde_json::to_value(&events[0]).unwrap();
+        assert_eq!(json["event"], "changed");
+        assert_eq!(json["server"]["name"], "TWO");
+        assert_eq!(json["changes"][1]["field"], "users_online");
+        assert_eq!(json["changes"][1]["old"], 2);
+    }
+
+    #[test]
+    fn it_keeps_servers_from_unreachable_trackers() {
+        let mut elsewhere = server(5501, "elsewhere", 0);
+        elsewhere.trackers = vec!["down.example.com".into()];
+        let old = vec![server(5500, "one", 1), elsewhere.clone()];
+
+        let mut new = vec![server(5500, "one", 1)];
+        carry_over(&old, &mut new, &HashSet::from(["down.example.com".into()]));
+
+        assert_eq!(new, vec![server(5500, "one", 1), elsewhere]);
+        assert!(diff(&old, &new).is_empty());
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use macroman_tools::MacRomanString;
+
+use serde::Deserialize;
+
+use thiserror::Error;
+
+/// who is allowed to list the servers on this tracker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccessMode {
+    /// anyone who isn't denied can list servers
+    Open,
+
+    /// only clients in the allow list can list servers
+    AllowOnly,
+
+    /// clients must send one of the configured passwords to list servers
+    Private,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AccessError {
+    #[error("address is in the deny list")]
+    Denied,
+
+    #[error("address is in the banlist")]
+    Banned,
+
+    #[error("address is not in the allow list")]
+    NotAllowed,
+
+    #[error("no password provided")]
+    PasswordRequired,
+
+    #[error("incorrect password")]
+    BadPassword,
+}
+
+#[derive(Debug, Error)]
+#[error("Invalid network: {0}")]
+pub struct InvalidNetwork(String);
+
+/// an IP address or a CIDR network, like `192.0.2.1` or `10.0.0.0/8`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNetwork {
+    pub fn contains(&self, addr: &IpAddr) -> bool {
+        match (self.addr, addr) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(*addr) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(addr)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(*addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = InvalidNetwork;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidNetwork(s.into());
+
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s, None),
+        };
+
+        let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len.parse().map_err(|_| invalid())?,
+            None => max_prefix_len,
+        };
+
+        if prefix_len > max_prefix_len {
+            return Err(invalid());
+        }
+
+        Ok(Self { addr, prefix_len })
+    }
+}
+
+/// decides which clients can list the servers on this tracker.
+#[derive(Debug)]
+pub struct AccessPolicy {
+    pub mode: AccessMode,
+
+    /// clients that can never list servers, regardless of mode
+    pub deny: Vec<IpNetwork>,
+
+    /// in allow-only mode, the clients that can list servers
+    pub allow: Vec<IpNetwork>,
+
+    /// in private mode, the passwords clients can use to list servers
+    pub passwords: Vec<String>,
+
+    /// also refuse clients whose address is in the registration banlist
+    pub use_banlist: bool,
+
+    /// a copy of the banlist, refreshed from the database while the tracker is running
+    pub banned: RwLock<HashSet<IpAddr>>,
+}
+
+impl Default for AccessPolicy {
+    fn default() -> Self {
+        Self {
+            mode: AccessMode::Open,
+            deny: vec![],
+            allow: vec![],
+            passwords: vec![],
+            use_banlist: false,
+            banned: RwLock::new(HashSet::new()),
+        }
+    }
+}
+
+impl AccessPolicy {
+    /// replace the snapshot of the banlist used when `use_banlist` is set
+    pub fn set_banned(&self, banned: HashSet<IpAddr>) {
+        *self.banned.write().unwrap() = banned;
+    }
+
+    /// check whether a client may connect at all. This is checked as soon as a connection is
+    /// accepted, before anything is read from the client.
+    pub fn check_address(&self, addr: &IpAddr) -> Result<(), AccessError> {
+        // IPv4 clients connecting to an IPv6 socket show up as IPv4-mapped addresses
+        let addr = addr.to_canonical();
+
+        if self.deny.iter().any(|network| network.contains(&addr)) {
+            return Err(AccessError::Denied);
+        }
+
+        if self.use_banlist && self.banned.read().unwrap().contains(&addr) {
+            return Err(AccessError::Banned);
+        }
+
+        if self.mode == AccessMode::AllowOnly
+            && !self.allow.iter().any(|network| network.contains(&addr))
+        {
+            return Err(AccessError::NotAllowed);
+        }
+
+        Ok(())
+    }
+
+    /// check the password sent by a client. This only matters for private trackers.
+    pub fn check_password(
+        &self,
+        password: Option<&MacRomanString<255>>,
+    ) -> Result<(), AccessError> {
+        if self.mode != AccessMode::Private {
+            return Ok(());
+        }
+
+        let password = password.ok_or(AccessError::PasswordRequired)?.as_string();
+
+        if self.passwords.contains(&password) {
+            Ok(())
+        } else {
+            Err(AccessError::BadPassword)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn it_matches_networks() {
+        let network: IpNetwork = "10.0.0.0/8".parse().unwrap();
+        assert!(network.contains(&ip("10.1.2.3")));
+        assert!(!network.contains(&ip("11.0.0.1")));
+
+        let network: IpNetwork = "192.0.2.1".parse().unwrap();
+        assert!(network.contains(&ip("192.0.2.1")));
+        assert!(!network.contains(&ip("192.0.2.2")));
+
+        let network: IpNetwork = "2001:db8::/32".parse().unwrap();
+        assert!(network.contains(&ip("2001:db8::1")));
+        assert!(!network.contains(&ip("10.0.0.1")));
+
+        let network: IpNetwork = "0.0.0.0/0".parse().unwrap();
+        assert!(network.contains(&ip("203.0.113.1")));
+
+        assert!("10.0.0.0/33".parse::<IpNetwork>().is_err());
+        assert!("tracker.example.com".parse::<IpNetwork>().is_err());
+    }
+
+    #[test]
+    fn it_applies_deny_allow_and_banlist() {
+        let policy = AccessPolicy {
+            mode: AccessMode::AllowOnly,
+            deny: vec!["10.0.0.66".parse().unwrap()],
+            allow: vec!["10.0.0.0/24".parse().unwrap()],
+            use_banlist: true,
+            ..Default::default()
+        };
+        policy.set_banned(HashSet::from([ip("10.0.0.13")]));
+
+        assert_eq!(policy.check_address(&ip("10.0.0.1")), Ok(()));
+        assert_eq!(policy.check_address(&ip("::ffff:10.0.0.1")), Ok(()));
+        assert_eq!(
+            policy.check_address(&ip("10.0.0.66")),
+            Err(AccessError::Denied)
+        );
+        assert_eq!(
+            policy.check_address(&ip("10.0.0.13")),
+            Err(AccessError::Banned)
+        );
+        assert_eq!(
+            policy.check_address(&ip("10.0.1.1")),
+            Err(AccessError::NotAllowed)
+        );
+    }
+
+    #[test]
+    fn it_requires_a_password_for_private_trackers() {
+        let policy = AccessPolicy {
+            mode: AccessMode::Private,
+            passwords: vec!["secret".into()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            policy.check_password(None),
+            Err(AccessError::PasswordRequired)
+        );
+        assert_eq!(
+            policy.check_password(Some(&"nope".into())),
+            Err(AccessError::BadPassword)
+        );
+        assert_eq!(policy.check_password(Some(&"secret".into())), Ok(()));
+
+        // open trackers don't care
+        assert_eq!(AccessPolicy::default().check_password(None), Ok(()));
+    }
+}
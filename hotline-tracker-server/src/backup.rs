@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+
+use diesel::prelude::*;
+
+use crate::banlist::Banlist;
+use crate::db::DbConnection;
+use crate::password::Password;
+
+/// the contents of the tracker database, used for `db export` and `db import` so the banlist and
+/// passwords can be backed up or moved between databases (including between sqlite and postgres).
+#[derive(Serialize, Deserialize)]
+pub struct Backup {
+    #[serde(default)]
+    pub banlist: Vec<Banlist>,
+
+    #[serde(default)]
+    pub passwords: Vec<Password>,
+}
+
+/// how many entries were added by an import. entries that already exist are skipped.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub banlist_added: usize,
+    pub banlist_skipped: usize,
+    pub passwords_added: usize,
+    pub passwords_skipped: usize,
+}
+
+impl Backup {
+    pub fn export(db: &mut DbConnection) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            banlist: Banlist::list(db)?,
+            passwords: Password::list(db)?,
+        })
+    }
+
+    /// write every entry to the database in a single transaction; if any insert fails, nothing is
+    /// imported.
+    pub fn import(
+        &self,
+        db: &mut DbConnection,
+    ) -> Result<ImportSummary, Box<dyn std::error::Error>> {
+        db.transaction(|db| {
+            let mut summary = ImportSummary::default();
+
+            for entry in &self.banlist {
+                if Banlist::restore(db, entry)? {
+                    summary.banlist_added += 1;
+                } else {
+                    summary.banlist_skipped += 1;
+                }
+            }
+
+            for entry in &self.passwords {
+                if Password::restore(db, entry)? {
+                    summary.passwords_added += 1;
+                } else {
+                    summary.passwords_skipped += 1;
+                }
+            }
+
+            Ok(summary)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::db::tests::test_db;
+
+    #[test]
+    fn it_round_trips_through_json() {
+        let Some(mut db) = test_db() else { return };
+
+        Banlist::add(&mut db, "10.0.0.1", "spammer").unwrap();
+        Password::add(&mut db, "hunter2", "for bob").unwrap();
+
+        let json = serde_json::to_string(&Backup::export(&mut db).unwrap()).unwrap();
+        let backup: Backup = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(backup.banlist[0].address, "10.0.0.1");
+        assert_eq!(backup.passwords[0].notes, "for bob");
+
+        // importing into the same database skips everything that's already there
+        Password::remove(&mut db, "hunter2").unwrap();
+        let summary = backup.import(&mut db).unwrap();
+
+        assert_eq!(
+            summary,
+            ImportSummary {
+                banlist_added: 0,
+                banlist_skipped: 1,
+                passwords_added: 1,
+                passwords_skipped: 0,
+            }
+        );
+        assert!(Password::is_authorized(&mut db, &"hunter2".into()).unwrap());
+    }
+}
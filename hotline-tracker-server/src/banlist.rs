@@ -1,13 +1,16 @@
 use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use super::schema::banlist;
 
 use std::net::Ipv4Addr;
 
+use crate::db::DbConnection;
 use crate::util::now;
 
-#[derive(Queryable)]
+#[derive(Queryable, Serialize, Deserialize)]
 pub struct Banlist {
+    #[serde(default)]
     pub id: i32,
     pub address: String,
     pub notes: String,
@@ -15,7 +18,7 @@ pub struct Banlist {
 }
 
 #[derive(Insertable)]
-#[table_name = "banlist"]
+#[diesel(table_name = banlist)]
 struct NewBanlistEntry<'a> {
     address: &'a str,
     notes: &'a str,
@@ -24,7 +27,7 @@ struct NewBanlistEntry<'a> {
 
 impl Banlist {
     pub fn is_banned(
-        db: &SqliteConnection,
+        db: &mut DbConnection,
         addr: &Ipv4Addr,
     ) -> Result<bool, Box<dyn std::error::Error>> {
         use crate::schema::banlist::dsl::*;
@@ -40,7 +43,7 @@ impl Banlist {
     }
 
     pub fn add(
-        db: &SqliteConnection,
+        db: &mut DbConnection,
         address: &str,
         notes: &str,
     ) -> Result<(), Box<dyn std::error::Error>> {
@@ -60,7 +63,7 @@ impl Banlist {
         Ok(())
     }
 
-    pub fn remove(db: &SqliteConnection, addr: &str) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn remove(db: &mut DbConnection, addr: &str) -> Result<(), Box<dyn std::error::Error>> {
         use crate::schema::banlist::dsl::*;
 
         diesel::delete(banlist.filter(address.eq(addr))).execute(db)?;
@@ -68,7 +71,7 @@ impl Banlist {
         Ok(())
     }
 
-    pub fn list(db: &SqliteConnection) -> Result<Vec<Banlist>, Box<dyn std::error::Error>> {
+    pub fn list(db: &mut DbConnection) -> Result<Vec<Banlist>, Box<dyn std::error::Error>> {
         use crate::schema::banlist::dsl::*;
 
         let results = banlist.load::<Banlist>(db)?;
@@ -76,7 +79,38 @@ impl Banlist {
         Ok(results)
     }
 
-    pub fn len(db: &SqliteConnection) -> Result<i64, Box<dyn std::error::Error>> {
+    /// insert an entry exported from another database, keeping its notes and timestamp. Returns
+    /// false without changing anything if the address is already present.
+    pub fn restore(
+        db: &mut DbConnection,
+        entry: &Banlist,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        use crate::schema::banlist::dsl::*;
+
+        let existing: i64 = banlist
+            .filter(address.eq(&entry.address))
+            .count()
+            .get_result(db)?;
+
+        if existing > 0 {
+            return Ok(false);
+        }
+
+        let new_entry = NewBanlistEntry {
+            address: &entry.address,
+            notes: &entry.notes,
+            created_at: entry.created_at.clone(),
+        };
+
+        diesel::insert_into(banlist)
+            .values(&new_entry)
+            .execute(db)?;
+
+        Ok(true)
+    }
+
+    #[allow(dead_code)]
+    pub fn len(db: &mut DbConnection) -> Result<i64, Box<dyn std::error::Error>> {
         use crate::schema::banlist::dsl::*;
 
         // FIXME: surely there's a better way to do this.
@@ -85,3 +119,26 @@ impl Banlist {
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::db::tests::test_db;
+
+    #[test]
+    fn it_bans_and_unbans() {
+        let Some(mut db) = test_db() else { return };
+
+        let addr = "10.0.0.1".parse().unwrap();
+
+        assert!(!Banlist::is_banned(&mut db, &addr).unwrap());
+
+        Banlist::add(&mut db, "10.0.0.1", "spammer").unwrap();
+        assert!(Banlist::is_banned(&mut db, &addr).unwrap());
+        assert_eq!(Banlist::len(&mut db).unwrap(), 1);
+
+        Banlist::remove(&mut db, "10.0.0.1").unwrap();
+        assert!(!Banlist::is_banned(&mut db, &addr).unwrap());
+    }
+}
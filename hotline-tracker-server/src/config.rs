@@ -2,10 +2,25 @@ use serde::Deserialize;
 
 use log::debug;
 
+use thiserror::Error;
+
+use crate::access_policy::{AccessMode, AccessPolicy, InvalidNetwork, IpNetwork};
+use crate::connection_limiter::ConnectionLimits;
+use crate::db;
+use crate::registration_listener::RegistrationListener;
+use crate::tracker_listener::TrackerListener;
+
 use std::fs;
-use std::path::PathBuf;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 pub const DEFAULT_CONFIG_FILENAME: &str = "tracker.toml";
+pub const DEFAULT_BIND_ADDRESS: &str = "0.0.0.0";
+#[cfg(all(feature = "sqlite", not(feature = "postgres")))]
+pub const DEFAULT_DATABASE_FILENAME: &str = "tracker.sqlite3";
+#[cfg(feature = "postgres")]
+pub const DEFAULT_DATABASE_URL: &str = "postgres://localhost/tracker";
 
 // load from (precidence):
 // cli argument
@@ -17,27 +32,92 @@ pub const DEFAULT_CONFIG_FILENAME: &str = "tracker.toml";
 // if no config is found, then use ~/.config/hotline/tracker.conf
 // database relative path is relative to config
 
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Invalid bind address: {0}")]
+    InvalidBindAddress(String),
+
+    #[error(transparent)]
+    InvalidNetwork(#[from] InvalidNetwork),
+
+    #[error("Couldn't find the database directory {path:?}: {source}")]
+    MissingDatabaseDirectory {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
 #[derive(Debug)]
 pub struct Config {
+    #[allow(dead_code)]
     pub loaded_from: Option<String>,
     pub base_path: PathBuf,
-    pub bind_address: String,
+    /// addresses to listen for tracker listing connections on (TCP)
+    pub tracker_addresses: Vec<SocketAddr>,
+    /// addresses to listen for server registrations on (UDP)
+    pub registration_addresses: Vec<SocketAddr>,
     pub require_password: bool,
     pub database: String,
+    /// timeouts and connection limits for tracker listing connections
+    pub limits: ConnectionLimits,
+    /// which clients are allowed to list servers
+    pub access: AccessPolicy,
 }
 
 #[derive(Deserialize)]
 pub struct ParsedConfig {
     server: ParsedServerConfig,
+    listing: Option<ParsedListingConfig>,
+}
+
+#[derive(Deserialize)]
+pub struct ParsedListingConfig {
+    pub access: Option<AccessMode>,
+    pub deny: Option<Vec<String>>,
+    pub allow: Option<Vec<String>>,
+    pub passwords: Option<Vec<String>>,
+    #[serde(rename = "use-banlist")]
+    pub use_banlist: Option<bool>,
+}
+
+/// bind addresses can be given as a single string or a list of strings
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum ParsedBindAddresses {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl ParsedBindAddresses {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            Self::One(addr) => vec![addr],
+            Self::Many(addrs) => addrs,
+        }
+    }
 }
 
 #[derive(Deserialize)]
 pub struct ParsedServerConfig {
     #[serde(rename = "bind-address")]
-    pub bind_address: Option<String>,
+    pub bind_address: Option<ParsedBindAddresses>,
+    #[serde(rename = "tracker-bind-address")]
+    pub tracker_bind_address: Option<ParsedBindAddresses>,
+    #[serde(rename = "registration-bind-address")]
+    pub registration_bind_address: Option<ParsedBindAddresses>,
     #[serde(rename = "require-password")]
     pub require_password: Option<bool>,
     pub database: Option<String>,
+    /// seconds to wait for a listing client to send its header
+    #[serde(rename = "read-timeout")]
+    pub read_timeout: Option<u64>,
+    /// seconds allowed for sending the listing to a client
+    #[serde(rename = "write-timeout")]
+    pub write_timeout: Option<u64>,
+    #[serde(rename = "max-connections")]
+    pub max_connections: Option<usize>,
+    #[serde(rename = "max-connections-per-ip")]
+    pub max_connections_per_ip: Option<usize>,
 }
 
 /// attempt to locate the tracker.toml file which contains the tracker server configuration. This
@@ -84,16 +164,23 @@ pub fn load(path: String) -> Result<Config, Box<dyn std::error::Error>> {
         debug!("{path}: Config does't exist. Using default config.");
 
         let base_path = config_path.parent().unwrap();
-        let mut database = base_path.to_path_buf();
-        database.push("tracker.sqlite3");
 
         // just return the default
         return Ok(Config {
             loaded_from: None,
             base_path: base_path.into(),
-            bind_address: "0.0.0.0".into(),
+            tracker_addresses: parse_bind_addresses(
+                &[DEFAULT_BIND_ADDRESS],
+                TrackerListener::TRACKER_LISTEN_PORT,
+            )?,
+            registration_addresses: parse_bind_addresses(
+                &[DEFAULT_BIND_ADDRESS],
+                RegistrationListener::REGISTRATION_LISTEN_PORT,
+            )?,
             require_password: false,
-            database: database.to_str().unwrap().into(),
+            database: default_database(base_path),
+            limits: ConnectionLimits::default(),
+            access: AccessPolicy::default(),
         });
     }
 
@@ -105,44 +192,223 @@ pub fn load(path: String) -> Result<Config, Box<dyn std::error::Error>> {
 
     let base_path = config_path.parent().unwrap();
     let server_config = parsed_config.server;
-    let database = server_config
-        .database
-        .map(|db| {
-            let database = PathBuf::from(&db);
-
-            if database.is_absolute() {
-                debug!("Using absolute database path.");
-                // it's absolute, let's use this value
-                db
-            } else {
-                debug!("Using relative database path.");
-
-                // it's relative... so it should be relative to our base_path
-                let mut database = base_path.to_path_buf();
-                database.push(db);
-                fs::canonicalize(&database)
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .into()
-            }
-        })
-        .unwrap_or_else(|| {
-            let mut database = base_path.to_path_buf();
-            database.push("tracker.sqlite3");
-            database.to_str().unwrap().into()
-        });
+    let database = match server_config.database {
+        Some(db) if db::is_url(&db) => {
+            debug!("Using database URL.");
+            db
+        }
+        Some(db) if Path::new(&db).is_absolute() => {
+            debug!("Using absolute database path.");
+            // it's absolute, let's use this value
+            db
+        }
+        Some(db) => {
+            debug!("Using relative database path.");
+            relative_database(base_path, &db)?
+        }
+        None => default_database(base_path),
+    };
 
+    // the tracker and registration listeners each use their own setting if it's there, otherwise
+    // they both fall back to the shared bind-address.
     let bind_address = server_config
         .bind_address
-        .unwrap_or_else(|| "0.0.0.0".into());
+        .map(ParsedBindAddresses::into_vec)
+        .unwrap_or_else(|| vec![DEFAULT_BIND_ADDRESS.into()]);
+    let tracker_bind_address = server_config
+        .tracker_bind_address
+        .map(ParsedBindAddresses::into_vec)
+        .unwrap_or_else(|| bind_address.clone());
+    let registration_bind_address = server_config
+        .registration_bind_address
+        .map(ParsedBindAddresses::into_vec)
+        .unwrap_or(bind_address);
+
+    let tracker_addresses =
+        parse_bind_addresses(&tracker_bind_address, TrackerListener::TRACKER_LISTEN_PORT)?;
+    let registration_addresses = parse_bind_addresses(
+        &registration_bind_address,
+        RegistrationListener::REGISTRATION_LISTEN_PORT,
+    )?;
+
     let require_password = server_config.require_password.unwrap_or(false);
 
+    let default_limits = ConnectionLimits::default();
+    let limits = ConnectionLimits {
+        read_timeout: server_config
+            .read_timeout
+            .map(Duration::from_secs)
+            .unwrap_or(default_limits.read_timeout),
+        write_timeout: server_config
+            .write_timeout
+            .map(Duration::from_secs)
+            .unwrap_or(default_limits.write_timeout),
+        max_connections: server_config
+            .max_connections
+            .unwrap_or(default_limits.max_connections),
+        max_connections_per_ip: server_config
+            .max_connections_per_ip
+            .unwrap_or(default_limits.max_connections_per_ip),
+    };
+
+    let access = parsed_config
+        .listing
+        .map(parse_access_policy)
+        .transpose()?
+        .unwrap_or_default();
+
     Ok(Config {
         loaded_from: Some(path),
         base_path: base_path.into(),
-        bind_address,
+        tracker_addresses,
+        registration_addresses,
         require_password,
         database,
+        limits,
+        access,
+    })
+}
+
+fn parse_access_policy(listing: ParsedListingConfig) -> Result<AccessPolicy, ConfigError> {
+    let parse_networks = |networks: Option<Vec<String>>| -> Result<Vec<IpNetwork>, ConfigError> {
+        networks
+            .unwrap_or_default()
+            .iter()
+            .map(|network| Ok(network.parse()?))
+            .collect()
+    };
+
+    Ok(AccessPolicy {
+        mode: listing.access.unwrap_or(AccessMode::Open),
+        deny: parse_networks(listing.deny)?,
+        allow: parse_networks(listing.allow)?,
+        passwords: listing.passwords.unwrap_or_default(),
+        use_banlist: listing.use_banlist.unwrap_or(false),
+        ..AccessPolicy::default()
     })
 }
+
+/// parse an address to bind a listener to. This is an IPv4 or IPv6 address with an optional port:
+/// `0.0.0.0`, `10.0.0.1:5498`, `::`, `[::1]` or `[::1]:5498`. If no port is given, `default_port` is
+/// used.
+pub fn parse_bind_address(addr: &str, default_port: u16) -> Result<SocketAddr, ConfigError> {
+    if let Ok(sockaddr) = addr.parse::<SocketAddr>() {
+        return Ok(sockaddr);
+    }
+
+    // no port, so it's either a bare address or a bracketed ipv6 address
+    let ip = addr
+        .strip_prefix('[')
+        .and_then(|addr| addr.strip_suffix(']'))
+        .unwrap_or(addr);
+
+    ip.parse::<IpAddr>()
+        .map(|ip| SocketAddr::new(ip, default_port))
+        .map_err(|_| ConfigError::InvalidBindAddress(addr.into()))
+}
+
+pub fn parse_bind_addresses<S: AsRef<str>>(
+    addrs: &[S],
+    default_port: u16,
+) -> Result<Vec<SocketAddr>, ConfigError> {
+    addrs
+        .iter()
+        .map(|addr| parse_bind_address(addr.as_ref(), default_port))
+        .collect()
+}
+
+/// the database to use if none is configured. For sqlite, this is a file adjacent to the config
+/// file. For postgres, it's taken from `DATABASE_URL`, falling back to a local `tracker` database.
+/// `db` relative to the config's directory. the database may not exist yet, so only the
+/// directory can be canonicalized.
+fn relative_database(base_path: &Path, db: &str) -> Result<String, ConfigError> {
+    // a config file given as a bare filename has an empty parent.
+    let base_path = match base_path.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => base_path,
+    };
+    let mut database =
+        fs::canonicalize(base_path).map_err(|source| ConfigError::MissingDatabaseDirectory {
+            path: base_path.into(),
+            source,
+        })?;
+    database.push(db);
+    Ok(database.to_string_lossy().into())
+}
+
+#[cfg(all(feature = "sqlite", not(feature = "postgres")))]
+fn default_database(base_path: &Path) -> String {
+    let mut database = base_path.to_path_buf();
+    database.push(DEFAULT_DATABASE_FILENAME);
+    database.to_str().unwrap().into()
+}
+
+#[cfg(feature = "postgres")]
+fn default_database(_base_path: &Path) -> String {
+    std::env::var("DATABASE_URL").unwrap_or_else(|_| DEFAULT_DATABASE_URL.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_bind_addresses() {
+        let parse = |addr| parse_bind_address(addr, 5498).unwrap().to_string();
+
+        assert_eq!(parse("0.0.0.0"), "0.0.0.0:5498");
+        assert_eq!(parse("10.0.0.1:6000"), "10.0.0.1:6000");
+        assert_eq!(parse("::"), "[::]:5498");
+        assert_eq!(parse("[::1]"), "[::1]:5498");
+        assert_eq!(parse("[fd00::1]:6000"), "[fd00::1]:6000");
+
+        assert!(parse_bind_address("tracker.example.com", 5498).is_err());
+        assert!(parse_bind_address("10.0.0.1:", 5498).is_err());
+    }
+
+    #[test]
+    fn it_rejects_a_missing_database_directory() {
+        let missing = Path::new("/nonexistent/hotline-tracker");
+        assert!(matches!(
+            relative_database(missing, "tracker.sqlite3"),
+            Err(ConfigError::MissingDatabaseDirectory { .. })
+        ));
+    }
+
+    #[test]
+    fn it_loads_a_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(DEFAULT_CONFIG_FILENAME);
+
+        fs::write(
+            &path,
+            r#"
+            [server]
+            bind-address = ["0.0.0.0", "::"]
+            registration-bind-address = "10.0.0.1:6000"
+            database = "/tmp/tracker.sqlite3"
+
+            [listing]
+            access = "allow-only"
+            allow = ["10.0.0.0/8"]
+            "#,
+        )
+        .unwrap();
+
+        let config = load(path.to_str().unwrap().into()).unwrap();
+
+        assert_eq!(
+            config.tracker_addresses,
+            vec![
+                "0.0.0.0:5498".parse::<SocketAddr>().unwrap(),
+                "[::]:5498".parse().unwrap()
+            ]
+        );
+        assert_eq!(
+            config.registration_addresses,
+            vec!["10.0.0.1:6000".parse::<SocketAddr>().unwrap()]
+        );
+        assert_eq!(config.access.mode, AccessMode::AllowOnly);
+        assert_eq!(config.access.allow, vec!["10.0.0.0/8".parse().unwrap()]);
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Duration;
+
+use thiserror::Error;
+
+/// limits applied to tracker listing connections so slow or abusive clients can't tie up the
+/// tracker.
+#[derive(Debug, Clone)]
+pub struct ConnectionLimits {
+    /// how long to wait for a client to send its header
+    pub read_timeout: Duration,
+
+    /// how long we'll spend sending the listing to a client
+    pub write_timeout: Duration,
+
+    /// the maximum number of listing connections being served at once
+    pub max_connections: usize,
+
+    /// the maximum number of listing connections being served at once for a single IP
+    pub max_connections_per_ip: usize,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self {
+            read_timeout: Duration::from_secs(10),
+            write_timeout: Duration::from_secs(30),
+            max_connections: 256,
+            max_connections_per_ip: 8,
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum LimitError {
+    #[error("Too many connections")]
+    TooManyConnections,
+
+    #[error("Too many connections from {0}")]
+    TooManyConnectionsFromAddress(IpAddr),
+}
+
+/// keeps track of open listing connections. This is shared by every `TrackerListener` so the
+/// limits apply to the tracker as a whole, not per listen address.
+#[derive(Debug)]
+pub struct ConnectionLimiter {
+    limits: ConnectionLimits,
+    connections: Arc<Semaphore>,
+    connections_per_ip: Mutex<HashMap<IpAddr, usize>>,
+}
+
+/// held for as long as a connection is open. The connection's slot is released when it's dropped.
+#[derive(Debug)]
+pub struct ConnectionPermit {
+    limiter: Arc<ConnectionLimiter>,
+    ip: IpAddr,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl ConnectionLimiter {
+    pub fn new(limits: ConnectionLimits) -> Self {
+        Self {
+            connections: Arc::new(Semaphore::new(limits.max_connections)),
+            connections_per_ip: Mutex::new(HashMap::new()),
+            limits,
+        }
+    }
+
+    pub fn limits(&self) -> &ConnectionLimits {
+        &self.limits
+    }
+
+    /// reserve a slot for a new connection from `ip`, or return an error if either limit has been
+    /// reached. This never waits; connections over the limit should just be dropped.
+    pub fn try_acquire(self: &Arc<Self>, ip: IpAddr) -> Result<ConnectionPermit, LimitError> {
+        let permit = self
+            .connections
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| LimitError::TooManyConnections)?;
+
+        let mut connections_per_ip = self.connections_per_ip.lock().unwrap();
+        // check before inserting so rejected addresses don't leave entries behind
+        let count = connections_per_ip.get(&ip).copied().unwrap_or(0);
+        if count >= self.limits.max_connections_per_ip {
+            return Err(LimitError::TooManyConnectionsFromAddress(ip));
+        }
+
+        *connections_per_ip.entry(ip).or_insert(0) += 1;
+
+        Ok(ConnectionPermit {
+            limiter: self.clone(),
+            ip,
+            _permit: permit,
+        })
+    }
+
+    fn release(&self, ip: IpAddr) {
+        let mut connections_per_ip = self.connections_per_ip.lock().unwrap();
+
+        if let Some(count) = connections_per_ip.get_mut(&ip) {
+            *count -= 1;
+
+            if *count == 0 {
+                connections_per_ip.remove(&ip);
+            }
+        }
+    }
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.limiter.release(self.ip);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_limits_connections() {
+        let limiter = Arc::new(ConnectionLimiter::new(ConnectionLimits {
+            max_connections: 3,
+            max_connections_per_ip: 2,
+            ..Default::default()
+        }));
+
+        let a: IpAddr = "10.0.0.1".parse().unwrap();
+        let b: IpAddr = "10.0.0.2".parse().unwrap();
+
+        let a1 = limiter.try_acquire(a).unwrap();
+        let _a2 = limiter.try_acquire(a).unwrap();
+        assert_eq!(
+            limiter.try_acquire(a).unwrap_err(),
+            LimitError::TooManyConnectionsFromAddress(a)
+        );
+
+        let _b1 = limiter.try_acquire(b).unwrap();
+        assert_eq!(
+            limiter.try_acquire(b).unwrap_err(),
+            LimitError::TooManyConnections
+        );
+
+        // closing a connection frees up its slot
+        drop(a1);
+        let _b2 = limiter.try_acquire(b).unwrap();
+        assert!(limiter.try_acquire(a).is_err());
+    }
+
+    #[test]
+    fn it_forgets_rejected_addresses() {
+        let limiter = Arc::new(ConnectionLimiter::new(ConnectionLimits {
+            max_connections: 3,
+            max_connections_per_ip: 0,
+            ..Default::default()
+        }));
+
+        for i in 0..10 {
+            let ip = IpAddr::from([10, 0, 0, i]);
+            assert!(limiter.try_acquire(ip).is_err());
+        }
+
+        assert!(limiter.connections_per_ip.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,184 @@
+//! The database backend is selected at compile time with the `sqlite` (default) and `postgres`
+//! cargo features. Everything else in the tracker talks to the database through `DbConnection` so
+//! it doesn't need to care which one is in use.
+
+use diesel::migration::MigrationSource;
+use diesel::prelude::*;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+
+use log::info;
+
+#[cfg(not(any(feature = "sqlite", feature = "postgres")))]
+compile_error!("hotline-tracker-server requires either the `sqlite` or `postgres` feature.");
+
+#[cfg(feature = "postgres")]
+pub type DbConnection = diesel::pg::PgConnection;
+
+#[cfg(all(feature = "sqlite", not(feature = "postgres")))]
+pub type DbConnection = diesel::sqlite::SqliteConnection;
+
+/// the name of the backend we were built with. used for logging.
+#[cfg(feature = "postgres")]
+pub const BACKEND_NAME: &str = "postgres";
+
+#[cfg(all(feature = "sqlite", not(feature = "postgres")))]
+pub const BACKEND_NAME: &str = "sqlite";
+
+/// the migrations in `migrations` (sqlite) or `migrations_postgres` are compiled into the binary so
+/// a fresh database can be set up without the diesel CLI.
+#[cfg(feature = "postgres")]
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations_postgres");
+
+#[cfg(all(feature = "sqlite", not(feature = "postgres")))]
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+/// the state of a single embedded migration, as reported by `migration_status()`
+pub struct MigrationStatus {
+    pub name: String,
+    pub applied: bool,
+}
+
+/// the table diesel records applied migrations in
+const MIGRATIONS_TABLE: &str = "__diesel_schema_migrations";
+
+#[cfg(feature = "postgres")]
+const TABLE_EXISTS_QUERY: &str =
+    "SELECT COUNT(*) AS count FROM information_schema.tables WHERE table_name = $1";
+
+#[cfg(all(feature = "sqlite", not(feature = "postgres")))]
+const TABLE_EXISTS_QUERY: &str =
+    "SELECT COUNT(*) AS count FROM sqlite_master WHERE type = 'table' AND name = ?";
+
+#[derive(QueryableByName)]
+struct Count {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    count: i64,
+}
+
+/// returns true if the given database string is a connection URL rather than a path on disk.
+/// sqlite databases are always paths, but postgres needs a URL like `postgres://user@host/db`
+pub fn is_url(database: &str) -> bool {
+    database.contains("://")
+}
+
+/// open a connection to the given database. For sqlite, this is the path to the database file;
+/// for postgres, it's a connection URL.
+pub fn establish(database: &str) -> Result<DbConnection, ConnectionError> {
+    DbConnection::establish(database)
+}
+
+/// apply any migrations that haven't been run against this database yet and return the names of
+/// the ones that were applied.
+pub fn run_migrations(db: &mut DbConnection) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let pending: Vec<String> = db
+        .pending_migrations(MIGRATIONS)
+        .map_err(|err| err as Box<dyn std::error::Error>)?
+        .iter()
+        .map(|m| m.name().to_string())
+        .collect();
+
+    for name in &pending {
+        info!("Applying migration: {name}");
+    }
+
+    db.run_pending_migrations(MIGRATIONS)
+        .map_err(|err| err as Box<dyn std::error::Error>)?;
+
+    Ok(pending)
+}
+
+/// whether any migrations have ever been run against this database. Asking diesel which
+/// migrations were applied creates its table, so this looks for the table first.
+fn has_migrations_table(db: &mut DbConnection) -> QueryResult<bool> {
+    let result: Count = diesel::sql_query(TABLE_EXISTS_QUERY)
+        .bind::<diesel::sql_types::Text, _>(MIGRATIONS_TABLE)
+        .get_result(db)?;
+
+    Ok(result.count > 0)
+}
+
+/// list every embedded migration along with whether it's been applied to this database. The
+/// database isn't modified.
+pub fn migration_status(
+    db: &mut DbConnection,
+) -> Result<Vec<MigrationStatus>, Box<dyn std::error::Error>> {
+    let applied = if has_migrations_table(db)? {
+        db.applied_migrations()
+            .map_err(|err| err as Box<dyn std::error::Error>)?
+    } else {
+        vec![]
+    };
+
+    let migrations =
+        MigrationSource::<<DbConnection as Connection>::Backend>::migrations(&MIGRATIONS)
+            .map_err(|err| err as Box<dyn std::error::Error>)?;
+
+    let status = migrations
+        .iter()
+        .map(|m| MigrationStatus {
+            name: m.name().to_string(),
+            applied: applied.contains(&m.name().version().as_owned()),
+        })
+        .collect();
+
+    Ok(status)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// open a fresh database to run tests against.
+    ///
+    /// sqlite uses an in-memory database. postgres requires `TRACKER_TEST_DATABASE_URL` to point
+    /// at a scratch database; if it's not set, this returns None and the test is skipped.
+    /// Migrations and tests run inside of a transaction that's never committed, so nothing is
+    /// left behind.
+    pub fn test_db() -> Option<DbConnection> {
+        #[cfg(feature = "postgres")]
+        let url = match std::env::var("TRACKER_TEST_DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => {
+                eprintln!("TRACKER_TEST_DATABASE_URL not set; skipping.");
+                return None;
+            }
+        };
+
+        #[cfg(all(feature = "sqlite", not(feature = "postgres")))]
+        let url = String::from(":memory:");
+
+        let mut db = establish(&url).unwrap();
+        db.begin_test_transaction().unwrap();
+        run_migrations(&mut db).unwrap();
+
+        Some(db)
+    }
+
+    #[test]
+    fn it_detects_urls() {
+        assert!(is_url("postgres://tracker@localhost/tracker"));
+        assert!(!is_url("/var/lib/hotline/tracker.sqlite3"));
+        assert!(!is_url("./tracker.sqlite3"));
+    }
+
+    #[test]
+    fn it_applies_all_migrations() {
+        let Some(mut db) = test_db() else { return };
+
+        let status = migration_status(&mut db).unwrap();
+
+        assert!(!status.is_empty());
+        assert!(status.iter().all(|m| m.applied));
+        assert!(run_migrations(&mut db).unwrap().is_empty());
+    }
+
+    #[cfg(all(feature = "sqlite", not(feature = "postgres")))]
+    #[test]
+    fn it_reports_status_without_creating_tables() {
+        let mut db = establish(":memory:").unwrap();
+
+        let status = migration_status(&mut db).unwrap();
+        assert!(status.iter().all(|m| !m.applied));
+        assert!(!has_migrations_table(&mut db).unwrap());
+    }
+}
@@ -1,13 +1,16 @@
 use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
 
+use crate::db::DbConnection;
 use crate::util::now;
 
 use super::schema::passwords;
 
 use macroman_tools::MacRomanString;
 
-#[derive(Queryable)]
+#[derive(Queryable, Serialize, Deserialize)]
 pub struct Password {
+    #[serde(default)]
     pub id: i32,
     pub password: String,
     pub notes: String,
@@ -15,7 +18,7 @@ pub struct Password {
 }
 
 #[derive(Insertable)]
-#[table_name = "passwords"]
+#[diesel(table_name = passwords)]
 struct NewPasswordEntry<'a> {
     password: &'a str,
     notes: &'a str,
@@ -24,7 +27,7 @@ struct NewPasswordEntry<'a> {
 
 impl Password {
     pub fn is_authorized(
-        db: &SqliteConnection,
+        db: &mut DbConnection,
         provided_password: &MacRomanString<255>,
     ) -> Result<bool, Box<dyn std::error::Error>> {
         use crate::schema::passwords::dsl::*;
@@ -40,7 +43,7 @@ impl Password {
     }
 
     pub fn add(
-        db: &SqliteConnection,
+        db: &mut DbConnection,
         password: &str,
         notes: &str,
     ) -> Result<(), Box<dyn std::error::Error>> {
@@ -58,7 +61,7 @@ impl Password {
     }
 
     pub fn remove(
-        db: &SqliteConnection,
+        db: &mut DbConnection,
         password_to_delete: &str,
     ) -> Result<(), Box<dyn std::error::Error>> {
         use crate::schema::passwords::dsl::*;
@@ -68,7 +71,7 @@ impl Password {
         Ok(())
     }
 
-    pub fn list(db: &SqliteConnection) -> Result<Vec<Password>, Box<dyn std::error::Error>> {
+    pub fn list(db: &mut DbConnection) -> Result<Vec<Password>, Box<dyn std::error::Error>> {
         use crate::schema::passwords::dsl::*;
 
         let results = passwords.load::<Password>(db)?;
@@ -76,7 +79,37 @@ impl Password {
         Ok(results)
     }
 
-    pub fn len(db: &SqliteConnection) -> Result<i64, Box<dyn std::error::Error>> {
+    /// insert an entry exported from another database, keeping its notes and timestamp. Returns
+    /// false without changing anything if the password is already present.
+    pub fn restore(
+        db: &mut DbConnection,
+        entry: &Password,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        use crate::schema::passwords::dsl::*;
+
+        let existing: i64 = passwords
+            .filter(password.eq(&entry.password))
+            .count()
+            .get_result(db)?;
+
+        if existing > 0 {
+            return Ok(false);
+        }
+
+        let new_entry = NewPasswordEntry {
+            password: &entry.password,
+            notes: &entry.notes,
+            created_at: entry.created_at.clone(),
+        };
+
+        diesel::insert_into(passwords)
+            .values(&new_entry)
+            .execute(db)?;
+
+        Ok(true)
+    }
+
+    pub fn len(db: &mut DbConnection) -> Result<i64, Box<dyn std::error::Error>> {
         use crate::schema::passwords::dsl::*;
 
         // FIXME: surely there's a better way to do this.
@@ -85,3 +118,21 @@ impl Password {
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::db::tests::test_db;
+
+    #[test]
+    fn it_authorizes_passwords() {
+        let Some(mut db) = test_db() else { return };
+
+        Password::add(&mut db, "hunter2", "").unwrap();
+
+        assert!(Password::is_authorized(&mut db, &"hunter2".into()).unwrap());
+        assert!(!Password::is_authorized(&mut db, &"hunter3".into()).unwrap());
+        assert_eq!(Password::list(&mut db).unwrap().len(), 1);
+    }
+}
@@ -4,13 +4,9 @@ use tokio::sync::mpsc::Sender;
 
 use hotline_tracker::RegistrationRecord;
 
-use thiserror::Error;
+use log::warn;
 
-#[derive(Error, Debug)]
-pub enum RegistrationListenerError {
-    #[error("No support for IPv6")]
-    UnsupportedProtocol,
-}
+use crate::util::bind_udp;
 
 pub struct RegistrationListener {
     socket: UdpSocket,
@@ -22,14 +18,10 @@ impl RegistrationListener {
     pub const REGISTRATION_LISTEN_PORT: u16 = 5499;
 
     pub async fn new(
-        addr: &str,
-        port: u16,
+        addr: SocketAddr,
         sender: Sender<(Ipv4Addr, RegistrationRecord)>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        let interface = addr.parse::<IpAddr>()?;
-        let sockaddr = SocketAddr::new(interface, port);
-
-        let socket = UdpSocket::bind(sockaddr).await?;
+        let socket = bind_udp(addr)?;
 
         Ok(Self {
             socket,
@@ -45,11 +37,18 @@ impl RegistrationListener {
             // TODO: have this return an Err if None.
             let r = RegistrationRecord::from_bytes(&self.buf[..len]).unwrap();
 
-            if let SocketAddr::V4(addr) = addr {
-                self.sender.send((*addr.ip(), r)).await?;
-            } else {
-                return Err(Box::new(RegistrationListenerError::UnsupportedProtocol));
-            }
+            // tracker listings only carry IPv4 addresses, so registrations that come in over IPv6
+            // can't be listed. IPv6 sockets are IPv6-only, so IPv4 servers never show up here as
+            // IPv4-mapped addresses; they need an IPv4 registration address of their own.
+            let ip = match addr.ip() {
+                IpAddr::V4(ip) => ip,
+                IpAddr::V6(_) => {
+                    warn!("Rejected record [IPv6 address]: {} @ {addr}", r.name);
+                    continue;
+                }
+            };
+
+            self.sender.send((ip, r)).await?;
         }
     }
 }
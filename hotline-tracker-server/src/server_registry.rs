@@ -76,8 +76,8 @@ impl ServerRegistry {
 
         let remaining_data_size: u16 = self
             .servers
-            .iter()
-            .map(|(_, entry)| entry.server.data_size() as u16)
+            .values()
+            .map(|entry| entry.server.data_size() as u16)
             .sum();
 
         let total_servers = self.servers.len() as u16;
@@ -92,9 +92,6 @@ impl ServerRegistry {
     }
 
     pub fn server_records(&mut self) -> Vec<ServerRecord> {
-        self.servers
-            .iter()
-            .map(|(_k, v)| v.server.clone())
-            .collect()
+        self.servers.values().map(|v| v.server.clone()).collect()
     }
 }
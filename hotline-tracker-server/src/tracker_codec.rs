@@ -1,7 +1,10 @@
+use hotline_tracker::header::HEADER_LEN;
 use hotline_tracker::{Header, TrackerPacket};
 use tokio_util::codec::{Decoder, Encoder};
 
-use bytes::BytesMut;
+use bytes::{Buf, BytesMut};
+
+use macroman_tools::MacRomanString;
 
 use thiserror::Error;
 
@@ -15,6 +18,13 @@ pub struct TrackerCodec {
     state: State,
 }
 
+/// what a client sends when it connects: a header, optionally followed by a password for private
+/// trackers.
+#[derive(Debug, PartialEq)]
+pub struct ListingRequest {
+    pub password: Option<MacRomanString<255>>,
+}
+
 impl TrackerCodec {
     pub fn new() -> Self {
         Self {
@@ -39,25 +49,56 @@ pub enum CodecError {
 }
 
 impl Decoder for TrackerCodec {
-    type Item = TrackerPacket;
+    type Item = ListingRequest;
     type Error = CodecError;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
         if self.state == State::Initialized {
             if let Some(header) = Header::from_bytes(src) {
                 if header.is_valid() {
+                    src.advance(HEADER_LEN);
+                    self.state = State::ReceivedHeader;
+                    return Ok(Some(ListingRequest { password: None }));
+                }
+
+                if header.is_valid_request() {
+                    // the password's length byte follows the header
+                    let Some(&password_len) = src.get(HEADER_LEN) else {
+                        return Ok(None);
+                    };
+                    let password_end = HEADER_LEN + 1 + password_len as usize;
+
+                    if src.len() < password_end {
+                        return Ok(None);
+                    }
+
+                    let password = src[HEADER_LEN + 1..password_end].into();
+                    src.advance(password_end);
                     self.state = State::ReceivedHeader;
-                    return Ok(Some(TrackerPacket::Header));
+                    return Ok(Some(ListingRequest {
+                        password: Some(password),
+                    }));
                 }
 
                 return Err(CodecError::InvalidHeader(header.magic_word, header.version));
             }
 
-            return Err(CodecError::NoHeader);
+            // not enough data for the header yet
+            return Ok(None);
         }
 
         Err(CodecError::UnexpectedData)
     }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.decode(src)? {
+            Some(packet) => Ok(Some(packet)),
+            None if self.state == State::Initialized && !src.is_empty() => {
+                Err(CodecError::NoHeader)
+            }
+            None => Ok(None),
+        }
+    }
 }
 
 impl Encoder<TrackerPacket> for TrackerCodec {
@@ -81,3 +122,33 @@ impl Encoder<TrackerPacket> for TrackerCodec {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_decodes_a_header_with_a_password() {
+        let mut codec = TrackerCodec::new();
+        let mut src = BytesMut::from(&b"HTRK\x00\x02\x06sec"[..]);
+
+        // wait for the rest of the password
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+
+        src.extend_from_slice(b"ret");
+        let request = codec.decode(&mut src).unwrap().unwrap();
+
+        assert_eq!(request.password.unwrap().as_string(), "secret");
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn it_decodes_a_plain_header() {
+        let mut codec = TrackerCodec::new();
+        let mut src = BytesMut::from(&b"HTRK\x00\x01"[..]);
+
+        let request = codec.decode(&mut src).unwrap().unwrap();
+
+        assert_eq!(request, ListingRequest { password: None });
+    }
+}
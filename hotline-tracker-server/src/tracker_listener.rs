@@ -1,77 +1,290 @@
-use std::net::{IpAddr, SocketAddr};
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{sleep, timeout, Duration};
 
+use crate::access_policy::{AccessError, AccessPolicy};
+use crate::connection_limiter::ConnectionLimiter;
 use crate::server_registry::ServerRegistry;
-use crate::tracker_codec::TrackerCodec;
+use crate::tracker_codec::{CodecError, TrackerCodec};
+use crate::util::bind_tcp;
 use hotline_tracker::TrackerPacket;
 
 use futures::{SinkExt, StreamExt};
 use tokio_util::codec::Framed;
 
-use log::{debug, info};
+use thiserror::Error;
+
+use log::{debug, info, warn};
+
+#[derive(Debug, Error)]
+pub enum ConnectionError {
+    #[error("Timed out waiting for header")]
+    ReadTimeout,
+
+    #[error("Timed out sending listing")]
+    WriteTimeout,
+
+    #[error("Client disconnected before sending header")]
+    Disconnected,
+
+    #[error("Access denied: {0}")]
+    AccessDenied(#[from] AccessError),
+
+    #[error("Bad request: {0}")]
+    Codec(#[from] CodecError),
+
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
+}
 
 pub struct TrackerListener {
     socket: TcpListener,
     registry: Arc<Mutex<ServerRegistry>>,
+    limiter: Arc<ConnectionLimiter>,
+    access: Arc<AccessPolicy>,
 }
 
 impl TrackerListener {
     pub const TRACKER_LISTEN_PORT: u16 = 5498;
 
     pub async fn new(
-        addr: &str,
-        port: u16,
+        addr: SocketAddr,
         registry: Arc<Mutex<ServerRegistry>>,
+        limiter: Arc<ConnectionLimiter>,
+        access: Arc<AccessPolicy>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        let interface = addr.parse::<IpAddr>()?;
-        let sockaddr = SocketAddr::new(interface, port);
-        let socket = TcpListener::bind(sockaddr).await?;
+        let socket = bind_tcp(addr)?;
 
-        Ok(Self { socket, registry })
+        Ok(Self {
+            socket,
+            registry,
+            limiter,
+            access,
+        })
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.socket.local_addr()
     }
 
     pub async fn listen(&self) -> Result<(), Box<dyn std::error::Error>> {
         loop {
-            let (socket, addr) = self.socket.accept().await?;
+            let (socket, addr) = match self.socket.accept().await {
+                Ok(conn) => conn,
+                Err(err) => {
+                    // this is usually running out of file descriptors. back off for a moment
+                    // rather than taking down the whole tracker.
+                    warn!("Failed to accept connection: {err}");
+                    sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+
+            if let Err(err) = self.access.check_address(&addr.ip()) {
+                info!("Rejected connection from {addr}: {err}");
+                continue;
+            }
+
+            let permit = match self.limiter.try_acquire(addr.ip()) {
+                Ok(permit) => permit,
+                Err(err) => {
+                    warn!("Rejected connection from {addr}: {err}");
+                    continue;
+                }
+            };
 
             let registry = self.registry.clone();
+            let limiter = self.limiter.clone();
+            let access = self.access.clone();
 
             tokio::spawn(async move {
-                let codec = TrackerCodec::new();
-                let mut framed_stream = Framed::new(socket, codec);
-
                 info!("got a connection from {addr}");
 
-                if framed_stream.next().await.unwrap().is_ok() {
-                    let (update, servers) = {
-                        let mut registry = registry.lock().unwrap();
-                        debug!("got header.");
-                        let update = registry.create_update_record();
-
-                        let servers = registry.server_records();
-
-                        (update, servers)
-                    };
-
-                    debug!("sending header and update");
-                    framed_stream.send(TrackerPacket::Header).await.unwrap();
-                    framed_stream
-                        .send(TrackerPacket::Update(update))
-                        .await
-                        .unwrap();
-
-                    // TODO: this is probably fine for the scale we're at today, but this should
-                    // emit updates in chunks.
-                    for s in servers {
-                        debug!("sending server record");
-                        framed_stream
-                            .send(TrackerPacket::Server(s.into()))
-                            .await
-                            .unwrap();
-                    }
+                if let Err(err) = handle_connection(socket, registry, &limiter, &access).await {
+                    warn!("Connection from {addr} failed: {err}");
                 }
+
+                drop(permit);
             });
         }
     }
 }
+
+async fn handle_connection(
+    socket: TcpStream,
+    registry: Arc<Mutex<ServerRegistry>>,
+    limiter: &ConnectionLimiter,
+    access: &AccessPolicy,
+) -> Result<(), ConnectionError> {
+    let limits = limiter.limits();
+
+    let codec = TrackerCodec::new();
+    let mut framed_stream = Framed::new(socket, codec);
+
+    let request = match timeout(limits.read_timeout, framed_stream.next()).await {
+        Err(_) => return Err(ConnectionError::ReadTimeout),
+        Ok(None) => return Err(ConnectionError::Disconnected),
+        Ok(Some(request)) => request?,
+    };
+
+    access.check_password(request.password.as_ref())?;
+
+    let (update, servers) = {
+        let mut registry = registry.lock().unwrap();
+        debug!("got header.");
+        let update = registry.create_update_record();
+
+        let servers = registry.server_records();
+
+        (update, servers)
+    };
+
+    let send_listing = async {
+        debug!("sending header and update");
+        framed_stream.feed(TrackerPacket::Header).await?;
+        framed_stream.feed(TrackerPacket::Update(update)).await?;
+
+        // TODO: this is probably fine for the scale we're at today, but this should
+        // emit updates in chunks.
+        for s in servers {
+            debug!("sending server record");
+            framed_stream.feed(TrackerPacket::Server(s.into())).await?;
+        }
+
+        framed_stream.flush().await
+    };
+
+    timeout(limits.write_timeout, send_listing)
+        .await
+        .map_err(|_| ConnectionError::WriteTimeout)??;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::access_policy::AccessMode;
+    use crate::connection_limiter::ConnectionLimits;
+
+    use hotline_tracker::RegistrationRecord;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    async fn start_listener(limits: ConnectionLimits) -> SocketAddr {
+        start_listener_with_access(limits, AccessPolicy::default()).await
+    }
+
+    async fn start_listener_with_access(
+        limits: ConnectionLimits,
+        access: AccessPolicy,
+    ) -> SocketAddr {
+        let registry = Arc::new(Mutex::new(ServerRegistry::new()));
+        registry.lock().unwrap().register(
+            "10.0.0.1".parse().unwrap(),
+            RegistrationRecord {
+                name: "Test server".into(),
+                ..Default::default()
+            },
+        );
+
+        let limiter = Arc::new(ConnectionLimiter::new(limits));
+        let listener = TrackerListener::new(
+            "127.0.0.1:0".parse().unwrap(),
+            registry,
+            limiter,
+            Arc::new(access),
+        )
+        .await
+        .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let _ = listener.listen().await;
+        });
+
+        addr
+    }
+
+    #[tokio::test]
+    async fn it_sends_the_listing() {
+        let addr = start_listener(ConnectionLimits::default()).await;
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(b"HTRK\x00\x01").await.unwrap();
+
+        let mut response = vec![];
+        client.read_to_end(&mut response).await.unwrap();
+
+        assert_eq!(&response[..6], b"HTRK\x00\x01");
+        assert!(response.ends_with(b"\x0bTest server\x00"));
+    }
+
+    #[tokio::test]
+    async fn it_drops_clients_that_never_send_a_header() {
+        let addr = start_listener(ConnectionLimits {
+            read_timeout: Duration::from_millis(50),
+            ..Default::default()
+        })
+        .await;
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(b"HT").await.unwrap();
+
+        let mut response = vec![];
+        let read = timeout(Duration::from_secs(5), client.read_to_end(&mut response))
+            .await
+            .unwrap();
+
+        assert!(read.is_ok());
+        assert!(response.is_empty());
+    }
+
+    #[tokio::test]
+    async fn it_limits_connections_per_ip() {
+        let addr = start_listener(ConnectionLimits {
+            max_connections_per_ip: 1,
+            ..Default::default()
+        })
+        .await;
+
+        // hold a connection open without sending a header
+        let _slow = TcpStream::connect(addr).await.unwrap();
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let _ = client.write_all(b"HTRK\x00\x01").await;
+
+        let mut response = vec![];
+        let _ = client.read_to_end(&mut response).await;
+
+        assert!(response.is_empty());
+    }
+
+    #[tokio::test]
+    async fn it_requires_a_password_for_private_trackers() {
+        let addr = start_listener_with_access(
+            ConnectionLimits::default(),
+            AccessPolicy {
+                mode: AccessMode::Private,
+                passwords: vec!["secret".into()],
+                ..Default::default()
+            },
+        )
+        .await;
+
+        let list = |header: &'static [u8]| async move {
+            let mut client = TcpStream::connect(addr).await.unwrap();
+            client.write_all(header).await.unwrap();
+
+            let mut response = vec![];
+            let _ = client.read_to_end(&mut response).await;
+            response
+        };
+
+        assert!(list(b"HTRK\x00\x01").await.is_empty());
+        assert!(list(b"HTRK\x00\x02\x04nope").await.is_empty());
+        assert!(!list(b"HTRK\x00\x02\x06secret").await.is_empty());
+    }
+}
@@ -5,6 +5,10 @@ pub const MAGIC_WORD: &[u8; MAGIC_WORD_LEN] = b"HTRK";
 pub const VERSION: u16 = 1;
 pub const HEADER_LEN: usize = 6;
 
+/// not part of the original protocol: a client sends this version in its header when the header
+/// is followed by a password (a length byte + MacRoman string) for a private tracker.
+pub const PASSWORD_VERSION: u16 = 2;
+
 #[derive(Debug)]
 pub struct Header {
     pub magic_word: [u8; MAGIC_WORD_LEN],
@@ -21,10 +25,28 @@ impl Default for Header {
 }
 
 impl Header {
+    pub fn with_password() -> Self {
+        Self {
+            version: PASSWORD_VERSION,
+            ..Self::default()
+        }
+    }
+
     pub fn is_valid(&self) -> bool {
         &self.magic_word == MAGIC_WORD && self.version == VERSION
     }
 
+    /// like `is_valid`, but also accepts the password header clients send to private trackers.
+    /// Only trackers should accept that; tracker replies are always version 1.
+    pub fn is_valid_request(&self) -> bool {
+        self.is_valid() || (&self.magic_word == MAGIC_WORD && self.has_password())
+    }
+
+    /// true if this header is followed by a password
+    pub fn has_password(&self) -> bool {
+        self.version == PASSWORD_VERSION
+    }
+
     pub fn from_bytes(mut bytes: &[u8]) -> Option<Self> {
         if bytes.remaining() < HEADER_LEN {
             return None;
@@ -56,3 +78,25 @@ impl Header {
         HEADER_LEN
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_only_accepts_password_headers_as_requests() {
+        let header = Header::default();
+        assert!(header.is_valid());
+        assert!(header.is_valid_request());
+
+        let header = Header::with_password();
+        assert!(!header.is_valid());
+        assert!(header.is_valid_request());
+
+        let header = Header {
+            magic_word: *b"HTTP",
+            version: PASSWORD_VERSION,
+        };
+        assert!(!header.is_valid_request());
+    }
+}
@@ -14,7 +14,7 @@ const REGISTRY_VERSION: u16 = 1;
 /// we only need to ensure that the packet is well-formed
 ///
 /// TODO: this should return an error when parsing from bytes
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RegistrationRecord {
     pub port: u16,
     pub users_online: u16,
@@ -137,8 +137,8 @@ mod tests {
             ..Default::default()
         };
 
-        let mut data = r.to_bytes();
-        let new_r = RegistrationRecord::from_bytes(&mut data).unwrap();
+        let data = r.to_bytes();
+        let new_r = RegistrationRecord::from_bytes(&data).unwrap();
 
         assert_eq!(r, new_r);
     }
@@ -70,12 +70,12 @@ impl ServerRecord {
 
         let name = bytes[..name_len].into();
         // dbg!(name);
-        bytes.advance(name_len as usize);
+        bytes.advance(name_len);
 
         let desc_len = bytes.get_u8() as usize;
         let description = bytes[..desc_len].into();
         // dbg!(description);
-        bytes.advance(desc_len as usize);
+        bytes.advance(desc_len);
 
         let server_record = Self {
             address,
@@ -1,6 +1,6 @@
 use bytes::{Buf, BufMut, BytesMut};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UpdateRecord {
     pub version: u16,
     pub remaining_data_size: u16,
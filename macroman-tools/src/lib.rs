@@ -249,6 +249,15 @@ pub fn char_to_macroman(c: char) -> u8 {
         .unwrap_or(c as u8)
 }
 
+/// like `char_to_macroman`, but `None` for characters MacRoman doesn't have instead of mangling them
+pub fn try_char_to_macroman(c: char) -> Option<u8> {
+    MACROMAN_TABLE
+        .iter()
+        .find(|(_, unicode)| *unicode == c)
+        .map(|(char, _)| *char)
+        .or_else(|| (c < ' ').then_some(c as u8))
+}
+
 pub fn macroman_to_string(s: &[u8]) -> String {
     s.iter().map(|c| macroman_to_char(*c)).collect()
 }
@@ -257,6 +266,13 @@ pub fn string_to_macroman(s: &str) -> Vec<u8> {
     s.chars().map(char_to_macroman).collect()
 }
 
+/// like `string_to_macroman`, but fails with the first character MacRoman doesn't have
+pub fn try_string_to_macroman(s: &str) -> Result<Vec<u8>, char> {
+    s.chars()
+        .map(|c| try_char_to_macroman(c).ok_or(c))
+        .collect()
+}
+
 // this approach was lifted from arrayvec::ArrayString
 
 #[derive(Debug, PartialEq, Clone)]
@@ -355,4 +371,18 @@ mod tests {
 
         assert_eq!(s, "spike".to_owned());
     }
+
+    #[test]
+    fn it_round_trips_every_byte() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let s = macroman_to_string(&bytes);
+
+        assert_eq!(s.chars().count(), 256);
+        assert_eq!(try_string_to_macroman(&s), Ok(bytes));
+        assert_eq!(
+            try_string_to_macroman("caf\u{e9} \u{3c0}"),
+            Ok(b"caf\x8e \xb9".to_vec())
+        );
+        assert_eq!(try_string_to_macroman("\u{65e5}\u{672c}"), Err('\u{65e5}'));
+    }
 }